@@ -0,0 +1,232 @@
+use crate::NodeDistIndex;
+use proto_pdk_api::{anyhow, AnyResult};
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const NODE_DIST_INDEX_URL: &str = "https://nodejs.org/download/release/index.json";
+pub const NODE_NIGHTLY_INDEX_URL: &str = "https://nodejs.org/download/nightly/index.json";
+pub const NODE_RELEASE_SCHEDULE_URL: &str =
+    "https://raw.githubusercontent.com/nodejs/Release/main/schedule.json";
+
+/// Return the cache file for a dist index. The cache is stored in the
+/// Node.js tool directory, so that it's shared between all plugins.
+pub fn get_dist_index_cache_path(url: &str) -> PathBuf {
+    match url {
+        NODE_DIST_INDEX_URL => get_cache_dir().join("index.json"),
+        NODE_NIGHTLY_INDEX_URL => get_cache_dir().join("nightly-index.json"),
+        // Mirrors may provide different artifacts, so are cached separately
+        _ => get_cache_path_for_url("index", url),
+    }
+}
+
+/// Return the cache file for a release schedule.
+pub fn get_release_schedule_cache_path(url: &str) -> PathBuf {
+    match url {
+        NODE_RELEASE_SCHEDULE_URL => get_cache_dir().join("schedule.json"),
+        // Otherwise changing the URL would continue to use the old schedule
+        _ => get_cache_path_for_url("schedule", url),
+    }
+}
+
+fn get_cache_dir() -> PathBuf {
+    PathBuf::from("/proto/tools/node/cache")
+}
+
+fn get_cache_path_for_url(prefix: &str, url: &str) -> PathBuf {
+    get_cache_dir().join(format!(
+        "{prefix}-{}.json",
+        url.split_once("://")
+            .map_or(url, |(_, path)| path)
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "-")
+    ))
+}
+
+/// Host functionality that's required to load remote files,
+/// which can only be provided by the plugins themselves.
+pub trait RemoteHost {
+    /// Whether proto is running in offline mode.
+    fn is_offline(&self) -> AnyResult<bool>;
+
+    /// Send a GET request, with an optional `If-Modified-Since` date,
+    /// and return the status code and body.
+    fn get(&self, url: &str, modified_since: Option<String>) -> AnyResult<(u16, String)>;
+
+    /// Log a debug message.
+    fn debug(&self, message: String);
+}
+
+/// Load the contents of a cached URL, or fetch it when the cache is missing
+/// or older than the TTL (in seconds). When revalidating, an `If-Modified-Since`
+/// date is sent, and the cache is reused if the content hasn't changed.
+/// While offline, or if the fetch fails, a stale cache is used as-is.
+pub fn load_with_cache(
+    host: &impl RemoteHost,
+    url: &str,
+    path: &Path,
+    ttl: u64,
+) -> AnyResult<String> {
+    let modified_at = path
+        .metadata()
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs());
+
+    let Some(modified_at) = modified_at else {
+        if host.is_offline()? {
+            return Err(anyhow!(
+                "Unable to fetch {url} while offline, as nothing is cached"
+            ));
+        }
+
+        let Some(content) = fetch_if_modified(host, url, None)? else {
+            return Err(anyhow!(
+                "Failed to fetch {url}, the content was not modified but nothing is cached"
+            ));
+        };
+
+        write_cache(path, &content)?;
+
+        return Ok(content);
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let cached = fs::read_to_string(path)?;
+
+    if host.is_offline()? || now.saturating_sub(modified_at) < ttl {
+        return Ok(cached);
+    }
+
+    match fetch_if_modified(host, url, Some(format_http_date(modified_at))) {
+        Ok(Some(content)) => {
+            write_cache(path, &content)?;
+
+            Ok(content)
+        }
+        // Not modified, so rewrite the cache to reset the TTL
+        Ok(None) => {
+            write_cache(path, &cached)?;
+
+            Ok(cached)
+        }
+        Err(error) => {
+            host.debug(format!(
+                "Failed to revalidate {url}, using the stale cache: {error}"
+            ));
+
+            Ok(cached)
+        }
+    }
+}
+
+/// Load and parse JSON with [`load_with_cache`]. If the cached content can't
+/// be parsed (it may have been truncated), the cache is removed and refetched.
+pub fn load_json_with_cache<T: DeserializeOwned>(
+    host: &impl RemoteHost,
+    url: &str,
+    path: &Path,
+    ttl: u64,
+) -> AnyResult<T> {
+    let content = load_with_cache(host, url, path, ttl)?;
+
+    match serde_json::from_str(&content) {
+        Ok(data) => Ok(data),
+        Err(error) => {
+            host.debug(format!(
+                "Failed to parse the cache for {url}, refetching: {error}"
+            ));
+
+            fs::remove_file(path)?;
+
+            Ok(serde_json::from_str(&load_with_cache(
+                host, url, path, ttl,
+            )?)?)
+        }
+    }
+}
+
+/// Load the dist index from the provided URL, using a cache that's
+/// shared between all plugins.
+pub fn load_dist_index(host: &impl RemoteHost, url: &str, ttl: u64) -> AnyResult<NodeDistIndex> {
+    load_json_with_cache(host, url, &get_dist_index_cache_path(url), ttl)
+}
+
+// Response headers aren't available to plugins, so an ETag can't be
+// captured, and the cache file's modified time is used for revalidation instead
+fn fetch_if_modified(
+    host: &impl RemoteHost,
+    url: &str,
+    modified_since: Option<String>,
+) -> AnyResult<Option<String>> {
+    match host.get(url, modified_since)? {
+        (304, _) => Ok(None),
+        (200..=299, body) => Ok(Some(body)),
+        (status, _) => Err(anyhow!("Failed to fetch {url}, received status {status}")),
+    }
+}
+
+// Multiple proto processes may load the same cache at once, so write to a
+// unique temporary file and rename it, so that a partial write is never read
+fn write_cache(path: &Path, content: &str) -> AnyResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos())
+        .unwrap_or_default();
+
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".{nanos}.tmp"));
+
+    let temp_path = path.with_file_name(temp_name);
+
+    fs::write(&temp_path, content)?;
+
+    if let Err(error) = fs::rename(&temp_path, path) {
+        let _ = fs::remove_file(&temp_path);
+
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+// Format a Unix timestamp as an IMF-fixdate, for example: Sun, 06 Nov 1994 08:49:37 GMT
+fn format_http_date(timestamp: u64) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let days = (timestamp / 86400) as i64;
+    let secs = timestamp % 86400;
+
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        // The epoch was a Thursday
+        WEEKDAYS[(days + 4).rem_euclid(7) as usize],
+        MONTHS[(month - 1) as usize],
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
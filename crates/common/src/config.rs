@@ -1,21 +1,161 @@
+use crate::NODE_RELEASE_SCHEDULE_URL;
+use proto_pdk_api::{anyhow, AnyResult, HostArch};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeBundledNpmMode {
+    #[default]
+    Download,
+    Copy,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeCorepackMode {
+    #[default]
+    Off,
+    Expose,
+    Enable,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeEolPolicy {
+    Ignore,
+    #[default]
+    Warn,
+    Strict,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeGlobalsMode {
+    #[default]
+    Shared,
+    PerMajor,
+    PerVersion,
+}
+
+impl NodeGlobalsMode {
+    /// Return the globals mode from the `PROTO_NODE_GLOBALS_MODE` environment
+    /// variable, falling back to the configured mode. The variable is shared by
+    /// the Node.js and package manager plugins, so that they always agree.
+    pub fn from_env_or(value: Option<String>, configured: Self) -> AnyResult<Self> {
+        match value.as_deref() {
+            Some("shared") => Ok(Self::Shared),
+            Some("per-major") => Ok(Self::PerMajor),
+            Some("per-version") => Ok(Self::PerVersion),
+            Some(value) => Err(anyhow!(
+                "Invalid PROTO_NODE_GLOBALS_MODE value \"{value}\", expected \"shared\", \"per-major\", or \"per-version\"."
+            )),
+            None => Ok(configured),
+        }
+    }
+
+    /// Return the globals directory for the provided Node.js version,
+    /// relative from the Node.js tool directory.
+    pub fn get_globals_dir(&self, version: &str) -> String {
+        let version = version.trim_start_matches('v');
+
+        match self {
+            Self::Shared => "globals".into(),
+            Self::PerMajor => format!("globals/{}", version.split('.').next().unwrap_or(version)),
+            Self::PerVersion => format!("globals/{version}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeImportSource {
+    Fnm,
+    N,
+    Nvm,
+    Volta,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeRangePolicy {
+    #[default]
+    Highest,
+    PreferLts,
+    LtsOnly,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeRangeStrategy {
+    #[default]
+    Highest,
+    Lowest,
+}
+
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct NodePluginConfig {
+    pub arch: Option<HostArch>,
     pub bundled_npm: bool,
+    pub bundled_npm_mode: NodeBundledNpmMode,
+    pub compile_cache: bool,
+    pub corepack: NodeCorepackMode,
     pub dist_url: String,
+    pub emulation_fallback: bool,
+    pub eol_policy: NodeEolPolicy,
+    pub eol_warning_days: u32,
+    pub globals_mode: NodeGlobalsMode,
+    pub import_from: Vec<NodeImportSource>,
+    pub index_cache_ttl: u64,
+    pub min_release_age: u32,
+    pub node_options: Vec<String>,
+    pub range_policy: NodeRangePolicy,
+    pub range_strategy: NodeRangeStrategy,
+    pub run_env: HashMap<String, String>,
+    pub schedule_url: String,
+    pub verify_install: bool,
 }
 
 impl Default for NodePluginConfig {
     fn default() -> Self {
         Self {
+            arch: None,
             bundled_npm: false,
+            bundled_npm_mode: NodeBundledNpmMode::default(),
+            compile_cache: false,
+            corepack: NodeCorepackMode::default(),
             dist_url: "https://nodejs.org/download/release/v{version}/{file}".into(),
+            emulation_fallback: true,
+            eol_policy: NodeEolPolicy::default(),
+            eol_warning_days: 90,
+            globals_mode: NodeGlobalsMode::default(),
+            import_from: vec![],
+            index_cache_ttl: 3600,
+            min_release_age: 0,
+            node_options: vec![],
+            range_policy: NodeRangePolicy::default(),
+            range_strategy: NodeRangeStrategy::default(),
+            run_env: HashMap::new(),
+            schedule_url: NODE_RELEASE_SCHEDULE_URL.into(),
+            verify_install: false,
         }
     }
 }
 
-#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 #[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
 pub struct NodeDepmanPluginConfig {
+    pub globals_mode: NodeGlobalsMode,
+    pub index_cache_ttl: u64,
     pub shared_globals_dir: bool,
 }
+
+impl Default for NodeDepmanPluginConfig {
+    fn default() -> Self {
+        Self {
+            globals_mode: NodeGlobalsMode::default(),
+            index_cache_ttl: 3600,
+            shared_globals_dir: false,
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use proto_pdk_api::AnyResult;
+use std::fs;
+use std::path::Path;
+
+/// Recursively copy the files in a directory to another directory.
+/// Symlinks are not copied.
+pub fn copy_dir_all(from: &Path, to: &Path) -> AnyResult<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            copy_dir_all(&entry.path(), &to.join(entry.file_name()))?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
+}
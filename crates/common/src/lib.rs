@@ -1,7 +1,19 @@
+mod cache;
 mod config;
+mod fs;
+mod module_version;
 mod node_dist;
 mod package_json;
+mod release_schedule;
+#[cfg(feature = "wasm")]
+mod remote;
 
+pub use cache::*;
 pub use config::*;
+pub use fs::*;
+pub use module_version::*;
 pub use node_dist::*;
 pub use package_json::*;
+pub use release_schedule::*;
+#[cfg(feature = "wasm")]
+pub use remote::*;
@@ -0,0 +1,75 @@
+use crate::{load_dist_index, RemoteHost, NODE_DIST_INDEX_URL};
+use proto_pdk_api::AnyResult;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Return the native addon ABI (`NODE_MODULE_VERSION`) of a Node.js version.
+/// Prefers the installed headers, as they don't require a network request,
+/// but Windows archives don't include them.
+pub fn get_module_version(
+    host: &impl RemoteHost,
+    tool_dir: &Path,
+    version: &str,
+    ttl: u64,
+) -> AnyResult<Option<u32>> {
+    let header = tool_dir.join("include/node/node_version.h");
+
+    if header.exists() {
+        for line in fs::read_to_string(&header)?.lines() {
+            if let Some(abi) = line
+                .trim()
+                .strip_prefix("#define NODE_MODULE_VERSION ")
+                .and_then(|abi| abi.trim().parse().ok())
+            {
+                return Ok(Some(abi));
+            }
+        }
+    }
+
+    Ok(load_dist_index(host, NODE_DIST_INDEX_URL, ttl)?
+        .find_version(version)
+        .and_then(|item| item.get_module_version()))
+}
+
+// Tracks the ABI that dependencies were installed with, keyed by project directory.
+// It's not stored in `node_modules`, as some commands (`npm ci`) remove it.
+fn get_module_version_state_path() -> PathBuf {
+    PathBuf::from("/proto/tools/node/node-abi.json")
+}
+
+// Real paths of virtual directories may have a trailing separator
+fn get_project_key(project_dir: &str) -> &str {
+    project_dir.trim_end_matches(['/', '\\'])
+}
+
+fn load_module_version_state() -> AnyResult<BTreeMap<String, u32>> {
+    let state_path = get_module_version_state_path();
+
+    if !state_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    Ok(serde_json::from_str(&fs::read_to_string(state_path)?)?)
+}
+
+/// Return the ABI that the project's dependencies were installed with.
+pub fn load_recorded_module_version(project_dir: &str) -> AnyResult<Option<u32>> {
+    Ok(load_module_version_state()?.remove(get_project_key(project_dir)))
+}
+
+/// Record the ABI that the project's dependencies are installed with.
+pub fn record_module_version(project_dir: &str, abi: u32) -> AnyResult<()> {
+    let state_path = get_module_version_state_path();
+    let mut state = load_module_version_state()?;
+
+    state.insert(get_project_key(project_dir).to_owned(), abi);
+
+    if let Some(parent) = state_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(state_path, serde_json::to_string_pretty(&state)?)?;
+
+    Ok(())
+}
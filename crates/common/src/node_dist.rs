@@ -1,15 +1,253 @@
+use crate::release_schedule::parse_date;
+use proto_pdk_api::{Version, VersionReq};
 use serde::Deserialize;
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum NodeDistLTS {
     Name(String),
     State(bool),
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct NodeDistVersion {
+    pub date: String, // YYYY-MM-DD
+    #[serde(default)]
+    pub files: Vec<String>, // Artifact keys, like "linux-x64" or "win-x64-zip"
     pub lts: NodeDistLTS,
-    pub npm: Option<String>, // No v prefix
-    pub version: String,     // With v prefix
+    pub modules: Option<String>, // NODE_MODULE_VERSION (ABI)
+    pub npm: Option<String>,     // No v prefix
+    pub openssl: Option<String>,
+    #[serde(default)]
+    pub security: bool,
+    pub uv: Option<String>,
+    pub v8: Option<String>,
+    pub version: String, // With v prefix
+    pub zlib: Option<String>,
+}
+
+impl NodeDistVersion {
+    /// Return the version without the `v` prefix.
+    pub fn get_version(&self) -> &str {
+        self.version.strip_prefix('v').unwrap_or(&self.version)
+    }
+
+    /// Return the lowercased LTS codename, if an LTS release.
+    pub fn get_lts_name(&self) -> Option<String> {
+        match &self.lts {
+            NodeDistLTS::Name(name) => Some(name.to_lowercase()),
+            NodeDistLTS::State(_) => None,
+        }
+    }
+
+    /// Return the release date as the number of days since the Unix epoch.
+    pub fn get_release_day(&self) -> Option<i64> {
+        parse_date(&self.date)
+    }
+
+    /// Return the native addon ABI version (`process.versions.modules`).
+    pub fn get_module_version(&self) -> Option<u32> {
+        self.modules.as_ref().and_then(|abi| abi.parse().ok())
+    }
+
+    /// Return true if the version satisfies any of the provided requirements.
+    pub fn matches_any(&self, reqs: &[VersionReq]) -> bool {
+        self.to_version()
+            .is_some_and(|version| reqs.iter().any(|req| req.matches(&version)))
+    }
+
+    pub fn is_lts(&self) -> bool {
+        matches!(self.lts, NodeDistLTS::Name(_))
+    }
+
+    pub fn to_version(&self) -> Option<Version> {
+        Version::parse(self.get_version()).ok()
+    }
+}
+
+/// The `index.json` file from a Node.js distribution host,
+/// for example: https://nodejs.org/download/release/index.json
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct NodeDistIndex {
+    pub versions: Vec<NodeDistVersion>,
+}
+
+impl NodeDistIndex {
+    /// Return a copy of the index without the releases that were published
+    /// after the provided day. Releases with an unknown date are kept.
+    pub fn released_by(&self, day: i64) -> NodeDistIndex {
+        NodeDistIndex {
+            versions: self
+                .versions
+                .iter()
+                .filter(|item| {
+                    item.get_release_day()
+                        .map_or(true, |released| released <= day)
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Return the highest stable version.
+    pub fn latest(&self) -> Option<&NodeDistVersion> {
+        find_highest(self.versions.iter())
+    }
+
+    /// Return the highest LTS version, across all LTS lines.
+    pub fn latest_lts(&self) -> Option<&NodeDistVersion> {
+        find_highest(self.versions.iter().filter(|item| item.is_lts()))
+    }
+
+    /// Return the highest version for the provided major line.
+    pub fn latest_in_major(&self, major: u64) -> Option<&NodeDistVersion> {
+        find_highest(
+            self.versions
+                .iter()
+                .filter(|item| item.to_version().is_some_and(|v| v.major == major)),
+        )
+    }
+
+    /// Return the highest version for the provided LTS codename (case-insensitive).
+    pub fn latest_for_lts(&self, codename: &str) -> Option<&NodeDistVersion> {
+        let codename = codename.to_lowercase();
+
+        find_highest(
+            self.versions
+                .iter()
+                .filter(|item| item.get_lts_name().is_some_and(|name| name == codename)),
+        )
+    }
+
+    /// Return the highest version that bundled the provided npm version.
+    pub fn latest_with_npm(&self, npm: &str) -> Option<&NodeDistVersion> {
+        find_highest(
+            self.versions
+                .iter()
+                .filter(|item| item.npm.as_deref() == Some(npm)),
+        )
+    }
+
+    /// Return the highest LTS version that satisfies any of the provided requirements.
+    pub fn latest_lts_matching(&self, reqs: &[VersionReq]) -> Option<&NodeDistVersion> {
+        find_highest(
+            self.versions
+                .iter()
+                .filter(|item| item.is_lts() && item.matches_any(reqs)),
+        )
+    }
+
+    /// Return the lowest version that satisfies any of the provided requirements.
+    pub fn lowest_matching(&self, reqs: &[VersionReq]) -> Option<&NodeDistVersion> {
+        find_lowest(self.versions.iter().filter(|item| item.matches_any(reqs)))
+    }
+
+    /// Return the lowest LTS version that satisfies any of the provided requirements.
+    pub fn lowest_lts_matching(&self, reqs: &[VersionReq]) -> Option<&NodeDistVersion> {
+        find_lowest(
+            self.versions
+                .iter()
+                .filter(|item| item.is_lts() && item.matches_any(reqs)),
+        )
+    }
+
+    /// Return the exact version, with or without a `v` prefix.
+    pub fn find_version(&self, version: &str) -> Option<&NodeDistVersion> {
+        let version = version.strip_prefix('v').unwrap_or(version);
+
+        self.versions
+            .iter()
+            .find(|item| item.get_version() == version)
+    }
+
+    /// Return all LTS codenames (lowercased), ordered from the newest line to the oldest.
+    pub fn lts_codenames(&self) -> Vec<String> {
+        let mut lines: Vec<(u64, String)> = vec![];
+
+        for item in &self.versions {
+            if let (Some(name), Some(version)) = (item.get_lts_name(), item.to_version()) {
+                if !lines.iter().any(|(_, line)| *line == name) {
+                    lines.push((version.major, name));
+                }
+            }
+        }
+
+        lines.sort_by(|a, b| b.0.cmp(&a.0));
+        lines.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// Return the highest release that was marked as containing security fixes.
+    pub fn latest_security(&self) -> Option<&NodeDistVersion> {
+        find_highest(self.security_releases())
+    }
+
+    /// Return the highest security release for the provided major line.
+    pub fn latest_security_in_major(&self, major: u64) -> Option<&NodeDistVersion> {
+        find_highest(
+            self.security_releases()
+                .filter(|item| item.to_version().is_some_and(|v| v.major == major)),
+        )
+    }
+
+    /// Return the highest version for the provided native addon ABI version.
+    pub fn latest_for_module_version(&self, abi: u32) -> Option<&NodeDistVersion> {
+        find_highest(
+            self.versions
+                .iter()
+                .filter(|item| item.get_module_version() == Some(abi)),
+        )
+    }
+
+    /// Return all native addon ABI versions, ordered from the newest to the oldest.
+    pub fn module_versions(&self) -> Vec<u32> {
+        let mut abis = self
+            .versions
+            .iter()
+            .filter_map(|item| item.get_module_version())
+            .collect::<Vec<_>>();
+
+        abis.sort_by(|a, b| b.cmp(a));
+        abis.dedup();
+        abis
+    }
+
+    /// Return all major lines, ordered from the newest line to the oldest.
+    pub fn majors(&self) -> Vec<u64> {
+        let mut majors = self
+            .versions
+            .iter()
+            .filter_map(|item| item.to_version().map(|v| v.major))
+            .collect::<Vec<_>>();
+
+        majors.sort_by(|a, b| b.cmp(a));
+        majors.dedup();
+        majors
+    }
+
+    /// Return all releases that were marked as containing security fixes.
+    pub fn security_releases(&self) -> impl Iterator<Item = &NodeDistVersion> {
+        self.versions.iter().filter(|item| item.security)
+    }
+}
+
+// The index is ordered, but don't rely on it when determining the highest
+fn find_highest<'a>(
+    versions: impl Iterator<Item = &'a NodeDistVersion>,
+) -> Option<&'a NodeDistVersion> {
+    versions
+        .filter_map(|item| item.to_version().map(|version| (version, item)))
+        .filter(|(version, _)| version.pre.is_empty())
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, item)| item)
+}
+
+fn find_lowest<'a>(
+    versions: impl Iterator<Item = &'a NodeDistVersion>,
+) -> Option<&'a NodeDistVersion> {
+    versions
+        .filter_map(|item| item.to_version().map(|version| (version, item)))
+        .filter(|(version, _)| version.pre.is_empty())
+        .min_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, item)| item)
 }
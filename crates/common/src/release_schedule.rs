@@ -0,0 +1,119 @@
+use proto_pdk_api::Version;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeReleasePhase {
+    Pending,
+    Current,
+    ActiveLts,
+    MaintenanceLts,
+    EndOfLife,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NodeReleaseLine {
+    pub codename: Option<String>,
+    pub end: String,                 // YYYY-MM-DD
+    pub lts: Option<String>,         // YYYY-MM-DD
+    pub maintenance: Option<String>, // YYYY-MM-DD
+    pub start: String,               // YYYY-MM-DD
+}
+
+impl NodeReleaseLine {
+    /// Return the phase of the release line on the provided day.
+    pub fn get_phase(&self, today: i64) -> NodeReleasePhase {
+        let reached = |date: &Option<String>| {
+            date.as_deref()
+                .and_then(parse_date)
+                .is_some_and(|day| today >= day)
+        };
+
+        if parse_date(&self.start).is_some_and(|day| today < day) {
+            NodeReleasePhase::Pending
+        } else if parse_date(&self.end).is_some_and(|day| today >= day) {
+            NodeReleasePhase::EndOfLife
+        } else if reached(&self.maintenance) {
+            // Odd lines enter maintenance without an LTS phase
+            if self.lts.is_some() {
+                NodeReleasePhase::MaintenanceLts
+            } else {
+                NodeReleasePhase::Current
+            }
+        } else if reached(&self.lts) {
+            NodeReleasePhase::ActiveLts
+        } else {
+            NodeReleasePhase::Current
+        }
+    }
+
+    /// Return the number of days until the line reaches end-of-life,
+    /// which will be negative if it already has.
+    pub fn days_until_end(&self, today: i64) -> Option<i64> {
+        parse_date(&self.end).map(|day| day - today)
+    }
+}
+
+/// The `schedule.json` file from the nodejs/Release repository.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct NodeReleaseSchedule {
+    pub lines: BTreeMap<String, NodeReleaseLine>, // v20, v0.12, etc
+}
+
+impl NodeReleaseSchedule {
+    /// Return the release line that the provided version belongs to.
+    pub fn find_line(&self, version: &Version) -> Option<&NodeReleaseLine> {
+        let key = if version.major == 0 {
+            format!("v0.{}", version.minor)
+        } else {
+            format!("v{}", version.major)
+        };
+
+        self.lines.get(&key)
+    }
+
+    /// Return the major lines that are in the provided phase on the provided day,
+    /// ordered from the newest line to the oldest. Pre-1.0 lines are excluded.
+    pub fn majors_in_phase(&self, phase: NodeReleasePhase, today: i64) -> Vec<u64> {
+        let mut majors = self
+            .lines
+            .iter()
+            .filter(|(_, line)| line.get_phase(today) == phase)
+            .filter_map(|(key, _)| key.strip_prefix('v')?.parse::<u64>().ok())
+            .collect::<Vec<_>>();
+
+        majors.sort_by(|a, b| b.cmp(a));
+        majors
+    }
+}
+
+/// Parse a `YYYY-MM-DD` date into the number of days since the Unix epoch.
+pub fn parse_date(value: &str) -> Option<i64> {
+    let mut parts = value.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.get(0..2)?.parse().ok()?;
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    Some(era * 146097 + doe - 719468)
+}
+
+/// Return the current number of days since the Unix epoch.
+pub fn today() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| (duration.as_secs() / 86400) as i64)
+        .unwrap_or_default()
+}
@@ -0,0 +1,33 @@
+use crate::RemoteHost;
+use extism_pdk::*;
+use proto_pdk::*;
+
+#[host_fn]
+extern "ExtismHost" {
+    fn get_env_var(key: &str) -> String;
+}
+
+/// Loads remote files through the plugin's host.
+pub struct Remote;
+
+impl RemoteHost for Remote {
+    fn is_offline(&self) -> AnyResult<bool> {
+        Ok(host_env!("PROTO_OFFLINE").is_some_and(|value| value == "1" || value == "true"))
+    }
+
+    fn get(&self, url: &str, modified_since: Option<String>) -> AnyResult<(u16, String)> {
+        let mut req = HttpRequest::new(url);
+
+        if let Some(since) = modified_since {
+            req = req.with_header("If-Modified-Since", since);
+        }
+
+        let res = fetch(req, None)?;
+
+        Ok((res.status_code(), String::from_bytes(&res.body())?))
+    }
+
+    fn debug(&self, message: String) {
+        debug!("{}", message);
+    }
+}
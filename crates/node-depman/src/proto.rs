@@ -1,15 +1,22 @@
 use crate::npm_registry::parse_registry_response;
 use crate::package_manager::PackageManager;
 use extism_pdk::*;
-use node_common::{NodeDepmanPluginConfig, NodeDistVersion, VoltaField};
+use node_common::{
+    copy_dir_all, get_module_version, record_module_version, NodeDepmanPluginConfig, NodeDistIndex,
+    NodeGlobalsMode, Remote, VoltaField, NODE_DIST_INDEX_URL,
+};
 use nodejs_package_json::PackageJson;
 use proto_pdk::*;
 use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 
 #[host_fn]
 extern "ExtismHost" {
     fn exec_command(input: Json<ExecCommandInput>) -> Json<ExecCommandOutput>;
+    fn from_virtual_path(path: String) -> String;
     fn get_env_var(key: &str) -> String;
+    fn to_virtual_path(path: String) -> String;
 }
 
 #[plugin_fn]
@@ -151,6 +158,44 @@ pub fn load_versions(Json(input): Json<LoadVersionsInput>) -> FnResult<Json<Load
         )?;
     }
 
+    // Map Node.js releases to the npm version that was bundled with them,
+    // so that npm can be pinned without installing Node.js first
+    if manager == PackageManager::Npm {
+        match load_dist_index() {
+            Ok(index) => {
+                let mut bundled = vec![
+                    ("node-latest".to_owned(), index.latest()),
+                    ("node-lts".to_owned(), index.latest_lts()),
+                ];
+
+                for major in index.majors() {
+                    bundled.push((format!("node-{major}"), index.latest_in_major(major)));
+                }
+
+                for codename in index.lts_codenames() {
+                    let item = index.latest_for_lts(&codename);
+                    bundled.push((format!("node-{codename}"), item));
+                }
+
+                for item in &index.versions {
+                    bundled.push((format!("node-{}", item.get_version()), Some(item)));
+                }
+
+                for (alias, item) in bundled {
+                    if let Some(npm) = item
+                        .and_then(|item| item.npm.as_deref())
+                        .and_then(|npm| UnresolvedVersionSpec::parse(npm).ok())
+                    {
+                        output.aliases.insert(alias, npm);
+                    }
+                }
+            }
+            Err(error) => {
+                debug!("Unable to load the Node.js dist index, skipping node-* aliases: {error}");
+            }
+        }
+    }
+
     output
         .aliases
         .insert("latest".into(), output.latest.clone().unwrap());
@@ -172,36 +217,30 @@ pub fn resolve_version(
             if input.initial.is_alias("bundled") {
                 debug!("Received the bundled alias, attempting to find a version");
 
-                let response: Vec<NodeDistVersion> =
-                    fetch_url("https://nodejs.org/download/release/index.json")?;
+                let index = load_dist_index()?;
                 let mut found_version = false;
 
                 // Infer from proto's environment variable
                 if let Some(node_version) = host_env!("PROTO_NODE_VERSION") {
-                    for node_release in &response {
-                        // Theirs starts with v, ours does not
-                        if node_release.version[1..] == node_version && node_release.npm.is_some() {
-                            output.version =
-                                Some(VersionSpec::parse(node_release.npm.as_ref().unwrap())?);
-                            found_version = true;
-                            break;
-                        }
+                    if let Some(npm) = index
+                        .find_version(&node_version)
+                        .and_then(|release| release.npm.as_ref())
+                    {
+                        output.version = Some(VersionSpec::parse(npm)?);
+                        found_version = true;
                     }
                 }
 
                 // Otherwise call the current `node` binary and infer from that
                 if !found_version {
                     let result = exec_command!("node", ["--version"]);
-                    let node_version = result.stdout.trim();
-
-                    for node_release in &response {
-                        // Both start with v
-                        if node_release.version == node_version && node_release.npm.is_some() {
-                            output.version =
-                                Some(VersionSpec::parse(node_release.npm.as_ref().unwrap())?);
-                            found_version = true;
-                            break;
-                        }
+
+                    if let Some(npm) = index
+                        .find_version(result.stdout.trim())
+                        .and_then(|release| release.npm.as_ref())
+                    {
+                        output.version = Some(VersionSpec::parse(npm)?);
+                        found_version = true;
                     }
                 }
 
@@ -245,6 +284,42 @@ fn get_archive_prefix(manager: &PackageManager, spec: &VersionSpec) -> String {
     "package".into()
 }
 
+// The Node.js plugin requests this when `bundled-npm-mode` is "copy"
+#[plugin_fn]
+pub fn native_install(
+    Json(input): Json<NativeInstallInput>,
+) -> FnResult<Json<NativeInstallOutput>> {
+    let manager = PackageManager::detect()?;
+
+    if manager == PackageManager::Npm {
+        if let Some(npm_dir) = host_env!("PROTO_NODE_BUNDLED_NPM_DIR") {
+            let npm_dir = virtual_path!(npm_dir);
+            let bundled_version = fs::read_to_string(npm_dir.join("package.json"))
+                .ok()
+                .and_then(|content| json::from_str::<PackageJson>(&content).ok())
+                .and_then(|package_json| package_json.version);
+
+            if bundled_version.is_some_and(|version| version == input.context.version.to_string()) {
+                debug!("Copying npm that comes bundled with Node.js");
+
+                copy_dir_all(&npm_dir, &input.install_dir)?;
+
+                return Ok(Json(NativeInstallOutput {
+                    installed: true,
+                    ..NativeInstallOutput::default()
+                }));
+            }
+
+            debug!("The bundled npm package doesn't match, falling back to a download");
+        }
+    }
+
+    Ok(Json(NativeInstallOutput {
+        skip_install: true,
+        ..NativeInstallOutput::default()
+    }))
+}
+
 #[plugin_fn]
 pub fn download_prebuilt(
     Json(input): Json<DownloadPrebuiltInput>,
@@ -362,7 +437,16 @@ pub fn locate_executables(
 
     let config = get_tool_config::<NodeDepmanPluginConfig>()?;
 
-    if config.shared_globals_dir {
+    let globals_mode = get_globals_mode(&config)?;
+
+    if globals_mode != NodeGlobalsMode::Shared {
+        if let Some(node_version) = host_env!("PROTO_NODE_VERSION") {
+            globals_lookup_dirs.push(format!(
+                "$PROTO_HOME/tools/node/{}/bin",
+                globals_mode.get_globals_dir(&node_version)
+            ));
+        }
+    } else if config.shared_globals_dir {
         globals_lookup_dirs.push("$PROTO_HOME/tools/node/globals/bin".into());
     }
 
@@ -375,38 +459,55 @@ pub fn locate_executables(
     }))
 }
 
+// The environment variable is shared with the Node.js plugin, so that
+// globals are redirected into the directory that it looks up
+fn get_globals_mode(config: &NodeDepmanPluginConfig) -> Result<NodeGlobalsMode, Error> {
+    NodeGlobalsMode::from_env_or(host_env!("PROTO_NODE_GLOBALS_MODE"), config.globals_mode)
+}
+
 #[plugin_fn]
 pub fn pre_run(Json(input): Json<RunHook>) -> FnResult<Json<RunHookResult>> {
     let mut result = RunHookResult::default();
 
-    let Some(globals_dir) = &input.globals_dir else {
-        return Ok(Json(result));
-    };
-
     let args = &input.passthrough_args;
     let config = get_tool_config::<NodeDepmanPluginConfig>()?;
 
-    if args.len() < 3 || !config.shared_globals_dir {
+    // Only a record for the Node.js plugin, so it should never prevent installs
+    if is_project_install(args) {
+        if let Err(error) = record_project_module_version(&config) {
+            debug!("Failed to record the native addon ABI: {error}");
+        }
+    }
+
+    if args.len() < 3 {
         return Ok(Json(result));
     }
 
+    // Includes trailing /bin folder
+    let globals_dir = match (get_globals_mode(&config)?, host_env!("PROTO_NODE_VERSION")) {
+        // Isolate globals to the active Node.js version, so that native
+        // addons are always compiled against a matching ABI
+        (mode, Some(node_version)) if mode != NodeGlobalsMode::Shared => {
+            let dir = PathBuf::from("/proto/tools/node")
+                .join(mode.get_globals_dir(&node_version))
+                .join("bin");
+
+            real_path!(buf, dir)
+        }
+        _ => match &input.globals_dir {
+            Some(globals_dir) if config.shared_globals_dir => globals_dir.real_path().unwrap(),
+            _ => {
+                return Ok(Json(result));
+            }
+        },
+    };
+
     let env = get_host_environment()?;
     let manager = PackageManager::detect()?;
 
-    // Includes trailing /bin folder
-    let globals_bin_dir = globals_dir
-        .real_path()
-        .unwrap()
-        .to_string_lossy()
-        .to_string();
+    let globals_bin_dir = globals_dir.to_string_lossy().to_string();
     // Parent directory, doesn't include /bin folder
-    let globals_root_dir = globals_dir
-        .real_path()
-        .unwrap()
-        .parent()
-        .unwrap()
-        .to_string_lossy()
-        .to_string();
+    let globals_root_dir = globals_dir.parent().unwrap().to_string_lossy().to_string();
 
     match manager {
         // npm install|add|etc -g <dep>
@@ -498,3 +599,42 @@ pub fn pre_run(Json(input): Json<RunHook>) -> FnResult<Json<RunHookResult>> {
 
     Ok(Json(result))
 }
+
+// Commands that install dependencies into the project's node_modules,
+// in all package managers (yarn without a command also installs)
+fn is_project_install(args: &[String]) -> bool {
+    args.first()
+        .is_some_and(|command| ["add", "ci", "i", "install"].contains(&command.as_str()))
+        && args
+            .iter()
+            .all(|arg| arg != "--global" && arg != "-g" && arg != "--location=global")
+}
+
+// Record the ABI that dependencies are installed with, so that the
+// Node.js plugin can warn when running a version with a different ABI
+fn record_project_module_version(config: &NodeDepmanPluginConfig) -> AnyResult<()> {
+    let Some(node_version) = host_env!("PROTO_NODE_VERSION") else {
+        return Ok(());
+    };
+
+    let Some(abi) = get_module_version(
+        &Remote,
+        &PathBuf::from("/proto/tools/node").join(&node_version),
+        &node_version,
+        config.index_cache_ttl,
+    )?
+    else {
+        return Ok(());
+    };
+
+    let project_dir = real_path!(buf, PathBuf::from("/cwd"));
+
+    record_module_version(&project_dir.to_string_lossy(), abi)
+}
+
+// Shares the cached index with the Node.js plugin
+fn load_dist_index() -> AnyResult<NodeDistIndex> {
+    let config = get_tool_config::<NodeDepmanPluginConfig>()?;
+
+    node_common::load_dist_index(&Remote, NODE_DIST_INDEX_URL, config.index_cache_ttl)
+}
@@ -1,7 +1,10 @@
-use node_common::NodeDepmanPluginConfig;
-use proto_pdk_api::RunHook;
+use node_common::{NodeDepmanPluginConfig, NodeGlobalsMode};
+use proto_pdk_api::{NativeInstallInput, NativeInstallOutput, RunHook, RunHookResult};
 use proto_pdk_test_utils::*;
+use serial_test::serial;
 use std::collections::HashMap;
+use std::env;
+use std::fs;
 use std::path::PathBuf;
 
 mod pre_run {
@@ -35,6 +38,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("npm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: false,
+                    ..Default::default()
                 });
             });
 
@@ -50,6 +54,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("npm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -68,6 +73,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("npm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -93,6 +99,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("npm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -122,6 +129,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("npm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -155,6 +163,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("pnpm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: false,
+                    ..Default::default()
                 });
             });
 
@@ -170,6 +179,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("pnpm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -188,6 +198,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("pnpm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -213,6 +224,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("pnpm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -240,6 +252,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("pnpm-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -273,6 +286,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("yarn-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: false,
+                    ..Default::default()
                 });
             });
 
@@ -288,6 +302,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("yarn-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -306,6 +321,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("yarn-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -331,6 +347,7 @@ mod pre_run {
             let plugin = sandbox.create_plugin_with_config("yarn-test", |config| {
                 config.tool_config(NodeDepmanPluginConfig {
                     shared_globals_dir: true,
+                    ..Default::default()
                 });
             });
 
@@ -350,4 +367,255 @@ mod pre_run {
             );
         }
     }
+
+    mod globals_mode {
+        use super::*;
+
+        fn run_with_mode(
+            sandbox: &ProtoSandbox,
+            manager: &str,
+            mode: NodeGlobalsMode,
+            args: &[&str],
+        ) -> RunHookResult {
+            let plugin = sandbox.create_plugin_with_config(manager, |config| {
+                config.tool_config(NodeDepmanPluginConfig {
+                    globals_mode: mode,
+                    ..Default::default()
+                });
+            });
+
+            plugin.pre_run(RunHook {
+                passthrough_args: args.iter().map(|arg| arg.to_string()).collect(),
+                ..RunHook::default()
+            })
+        }
+
+        // Env vars are process-wide, so keep all assertions in a single test
+        #[test]
+        #[serial]
+        fn redirects_to_node_version_globals_dir() {
+            let sandbox = create_empty_proto_sandbox();
+            let globals_dir = sandbox.path().join(".proto/tools/node/globals");
+
+            env::set_var("PROTO_NODE_VERSION", "20.15.1");
+
+            let npm_major = run_with_mode(
+                &sandbox,
+                "npm-test",
+                NodeGlobalsMode::PerMajor,
+                &["install", "-g", "typescript"],
+            );
+            let npm_version = run_with_mode(
+                &sandbox,
+                "npm-test",
+                NodeGlobalsMode::PerVersion,
+                &["install", "-g", "typescript"],
+            );
+            let pnpm_major = run_with_mode(
+                &sandbox,
+                "pnpm-test",
+                NodeGlobalsMode::PerMajor,
+                &["add", "-g", "typescript"],
+            );
+            let shared = run_with_mode(
+                &sandbox,
+                "npm-test",
+                NodeGlobalsMode::Shared,
+                &["install", "-g", "typescript"],
+            );
+
+            env::remove_var("PROTO_NODE_VERSION");
+
+            let unknown_version = run_with_mode(
+                &sandbox,
+                "npm-test",
+                NodeGlobalsMode::PerMajor,
+                &["install", "-g", "typescript"],
+            );
+
+            let npm_prefix = |dir: PathBuf| {
+                if cfg!(windows) { dir.join("bin") } else { dir }
+                    .to_string_lossy()
+                    .to_string()
+            };
+
+            assert_eq!(
+                npm_major.env,
+                Some(HashMap::from_iter([(
+                    "PREFIX".into(),
+                    npm_prefix(globals_dir.join("20"))
+                )]))
+            );
+            assert_eq!(
+                npm_version.env,
+                Some(HashMap::from_iter([(
+                    "PREFIX".into(),
+                    npm_prefix(globals_dir.join("20.15.1"))
+                )]))
+            );
+            assert_eq!(
+                pnpm_major.args,
+                Some(vec![
+                    "--global-dir".into(),
+                    globals_dir.join("20").to_string_lossy().to_string(),
+                    "--global-bin-dir".into(),
+                    globals_dir.join("20/bin").to_string_lossy().to_string(),
+                ])
+            );
+            assert_eq!(shared.env, None);
+            assert_eq!(unknown_version.env, None);
+        }
+
+        #[test]
+        #[serial]
+        fn redirects_with_globals_mode_from_env_var() {
+            let sandbox = create_empty_proto_sandbox();
+            let globals_dir = sandbox.path().join(".proto/tools/node/globals/20");
+
+            env::set_var("PROTO_NODE_VERSION", "20.15.1");
+            env::set_var("PROTO_NODE_GLOBALS_MODE", "per-major");
+
+            let result = run_with_mode(
+                &sandbox,
+                "npm-test",
+                NodeGlobalsMode::Shared,
+                &["install", "-g", "typescript"],
+            );
+
+            env::remove_var("PROTO_NODE_VERSION");
+            env::remove_var("PROTO_NODE_GLOBALS_MODE");
+
+            assert_eq!(
+                result.env,
+                Some(HashMap::from_iter([(
+                    "PREFIX".into(),
+                    if cfg!(windows) {
+                        globals_dir.join("bin")
+                    } else {
+                        globals_dir
+                    }
+                    .to_string_lossy()
+                    .to_string()
+                )]))
+            );
+        }
+    }
+}
+
+mod module_version {
+    use super::*;
+
+    fn run_with_node(sandbox: &ProtoSandbox, args: &[&str]) -> Option<HashMap<String, u32>> {
+        let plugin = sandbox.create_plugin("npm-test");
+
+        sandbox.create_file(
+            ".proto/tools/node/20.0.0/include/node/node_version.h",
+            "#define NODE_MINOR_VERSION 0\n#define NODE_MODULE_VERSION 115\n",
+        );
+
+        env::set_var("PROTO_NODE_VERSION", "20.0.0");
+
+        plugin.pre_run(RunHook {
+            passthrough_args: args.iter().map(|arg| arg.to_string()).collect(),
+            ..RunHook::default()
+        });
+
+        env::remove_var("PROTO_NODE_VERSION");
+
+        fs::read_to_string(sandbox.path().join(".proto/tools/node/node-abi.json"))
+            .ok()
+            .map(|content| serde_json::from_str(&content).unwrap())
+    }
+
+    #[test]
+    #[serial]
+    fn records_abi_when_installing() {
+        for args in [vec!["install"], vec!["ci"], vec!["add", "react"]] {
+            let sandbox = create_empty_proto_sandbox();
+
+            assert_eq!(
+                run_with_node(&sandbox, &args),
+                Some(HashMap::from_iter([(
+                    sandbox.path().to_string_lossy().to_string(),
+                    115
+                )]))
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn doesnt_record_abi_for_other_commands() {
+        for args in [
+            vec![],
+            vec!["run", "build"],
+            vec!["install", "-g", "typescript"],
+        ] {
+            let sandbox = create_empty_proto_sandbox();
+
+            assert_eq!(run_with_node(&sandbox, &args), None);
+        }
+    }
+}
+
+mod native_install {
+    use super::*;
+
+    fn install(sandbox: &ProtoSandbox, manager: &str, version: &str) -> NativeInstallOutput {
+        let plugin = sandbox.create_plugin(manager);
+
+        plugin.native_install(NativeInstallInput {
+            context: ToolContext {
+                version: VersionSpec::parse(version).unwrap(),
+                ..Default::default()
+            },
+            install_dir: VirtualPath::WithReal {
+                path: PathBuf::from(format!("/proto/tools/{manager}/{version}")),
+                virtual_prefix: PathBuf::from("/proto"),
+                real_prefix: sandbox.path().join(".proto"),
+            },
+        })
+    }
+
+    // Env vars are process-wide, so keep all assertions in a single test
+    #[test]
+    #[serial]
+    fn copies_npm_bundled_with_node() {
+        let sandbox = create_empty_proto_sandbox();
+        let npm_dir = sandbox
+            .path()
+            .join(".proto/tools/node/20.0.0/lib/node_modules/npm");
+
+        sandbox.create_file(
+            ".proto/tools/node/20.0.0/lib/node_modules/npm/package.json",
+            r#"{ "name": "npm", "version": "9.6.4" }"#,
+        );
+        sandbox.create_file(
+            ".proto/tools/node/20.0.0/lib/node_modules/npm/bin/npm-cli.js",
+            "",
+        );
+
+        let not_requested = install(&sandbox, "npm-test", "9.6.4");
+
+        env::set_var("PROTO_NODE_BUNDLED_NPM_DIR", npm_dir);
+
+        let mismatched = install(&sandbox, "npm-test", "10.0.0");
+        let not_npm = install(&sandbox, "pnpm-test", "9.6.4");
+        let copied = install(&sandbox, "npm-test", "9.6.4");
+
+        env::remove_var("PROTO_NODE_BUNDLED_NPM_DIR");
+
+        for output in [not_requested, mismatched, not_npm] {
+            assert!(!output.installed);
+            assert!(output.skip_install);
+        }
+
+        assert!(copied.installed);
+        assert!(sandbox
+            .path()
+            .join(".proto/tools/npm-test/9.6.4/bin/npm-cli.js")
+            .exists());
+        assert!(!sandbox.path().join(".proto/tools/npm-test/10.0.0").exists());
+        assert!(!sandbox.path().join(".proto/tools/pnpm-test").exists());
+    }
 }
@@ -1,4 +1,6 @@
 use proto_pdk_test_utils::*;
+use serial_test::serial;
+use std::env;
 
 mod npm {
     use super::*;
@@ -9,6 +11,81 @@ mod npm {
         "9.7.2" => "9.7.2",
     });
 
+    #[test]
+    #[serial]
+    fn resolves_bundled_from_cached_node_index() {
+        let sandbox = create_empty_proto_sandbox();
+        let plugin = sandbox.create_plugin("npm-test");
+
+        sandbox.create_file(
+            ".proto/tools/node/cache/index.json",
+            r#"[{ "version": "v99.0.0", "date": "2099-01-01", "lts": false, "npm": "99.1.0" }]"#,
+        );
+
+        env::set_var("PROTO_NODE_VERSION", "99.0.0");
+
+        let output = plugin.resolve_version(ResolveVersionInput {
+            initial: UnresolvedVersionSpec::Alias("bundled".into()),
+        });
+
+        env::remove_var("PROTO_NODE_VERSION");
+
+        assert_eq!(output.version, Some(VersionSpec::parse("99.1.0").unwrap()));
+    }
+
+    #[test]
+    fn sets_node_bundled_aliases() {
+        let sandbox = create_empty_proto_sandbox();
+        let plugin = sandbox.create_plugin("npm-test");
+
+        sandbox.create_file(
+            ".proto/tools/node/cache/index.json",
+            r#"[
+  { "version": "v20.1.0", "date": "2023-05-03", "lts": false, "npm": "9.6.4" },
+  { "version": "v18.19.0", "date": "2023-11-29", "lts": "Hydrogen", "npm": "10.2.3" },
+  { "version": "v18.18.0", "date": "2023-09-18", "lts": "Hydrogen", "npm": "9.8.1" }
+]"#,
+        );
+
+        let output = plugin.load_versions(LoadVersionsInput::default());
+
+        for (alias, npm) in [
+            ("node-latest", "9.6.4"),
+            ("node-lts", "10.2.3"),
+            ("node-hydrogen", "10.2.3"),
+            ("node-20", "9.6.4"),
+            ("node-18", "10.2.3"),
+            ("node-18.18.0", "9.8.1"),
+        ] {
+            assert_eq!(
+                output.aliases.get(alias),
+                Some(&UnresolvedVersionSpec::parse(npm).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn skips_node_bundled_aliases_when_index_unavailable() {
+        let sandbox = create_empty_proto_sandbox();
+        let plugin = sandbox.create_plugin("npm-test");
+
+        // An invalid cache is refetched, which isn't possible while offline
+        sandbox.create_file(".proto/tools/node/cache/index.json", "{");
+
+        env::set_var("PROTO_OFFLINE", "1");
+
+        let output = plugin.load_versions(LoadVersionsInput::default());
+
+        env::remove_var("PROTO_OFFLINE");
+
+        assert!(output.aliases.contains_key("latest"));
+        assert!(!output
+            .aliases
+            .keys()
+            .any(|alias| alias.starts_with("node-")));
+    }
+
     #[test]
     fn doesnt_parse_package_manager_if_diff_name() {
         let sandbox = create_empty_proto_sandbox();
@@ -1,11 +1,24 @@
 use extism_pdk::*;
-use node_common::{NodeDistLTS, NodeDistVersion, NodePluginConfig, VoltaField};
+use node_common::{
+    copy_dir_all, get_module_version, get_release_schedule_cache_path, load_json_with_cache,
+    load_recorded_module_version, today, NodeBundledNpmMode, NodeCorepackMode, NodeDistIndex,
+    NodeEolPolicy, NodeGlobalsMode, NodeImportSource, NodePluginConfig, NodeRangePolicy,
+    NodeRangeStrategy, NodeReleasePhase, NodeReleaseSchedule, Remote, VoltaField,
+    NODE_DIST_INDEX_URL,
+};
 use nodejs_package_json::PackageJson;
 use proto_pdk::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
 
 #[host_fn]
 extern "ExtismHost" {
     fn exec_command(input: Json<ExecCommandInput>) -> Json<ExecCommandOutput>;
+    fn from_virtual_path(path: String) -> String;
+    fn get_env_var(key: &str) -> String;
+    fn host_log(input: Json<HostLogInput>);
+    fn to_virtual_path(path: String) -> String;
 }
 
 static NAME: &str = "Node.js";
@@ -13,9 +26,21 @@ static BIN: &str = "node";
 
 #[plugin_fn]
 pub fn register_tool(Json(_): Json<ToolMetadataInput>) -> FnResult<Json<ToolMetadataOutput>> {
+    let env = get_host_environment()?;
+    let config = get_tool_config::<NodePluginConfig>()?;
+
     Ok(Json(ToolMetadataOutput {
         name: NAME.into(),
         type_of: PluginType::Language,
+        // When overriding the architecture, install into a different
+        // directory so that multiple architectures can co-exist
+        inventory: ToolInventoryMetadata {
+            version_suffix: config
+                .arch
+                .filter(|arch| *arch != env.arch)
+                .map(|arch| format!("-{arch}")),
+            ..ToolInventoryMetadata::default()
+        },
         plugin_version: Some(env!("CARGO_PKG_VERSION").into()),
         ..ToolMetadataOutput::default()
     }))
@@ -70,43 +95,244 @@ pub fn parse_version_file(
         }
     }
 
+    if let Some(spec) = &version {
+        let reqs = get_version_reqs(spec);
+
+        if !reqs.is_empty() && get_range_strategy()? == NodeRangeStrategy::Lowest {
+            let config = get_tool_config::<NodePluginConfig>()?;
+            let index = apply_release_age(load_dist_index(NODE_DIST_INDEX_URL, &config)?, &config);
+
+            let lowest = match config.range_policy {
+                NodeRangePolicy::Highest => index.lowest_matching(&reqs),
+                NodeRangePolicy::PreferLts => index
+                    .lowest_lts_matching(&reqs)
+                    .or_else(|| index.lowest_matching(&reqs)),
+                NodeRangePolicy::LtsOnly => {
+                    let Some(item) = index.lowest_lts_matching(&reqs) else {
+                        return Err(plugin_err!(PluginError::Message(format!(
+                            "No {NAME} LTS version satisfies {spec}. Set range-policy to \"prefer-lts\" to allow non-LTS versions."
+                        ))));
+                    };
+
+                    Some(item)
+                }
+            };
+
+            if let Some(item) = lowest {
+                debug!(
+                    "Resolved {spec} to the lowest satisfying version {}",
+                    item.version
+                );
+
+                version = Some(UnresolvedVersionSpec::parse(item.get_version())?);
+            }
+        }
+    }
+
     Ok(Json(ParseVersionFileOutput { version }))
 }
 
+// Releases within the cooldown period are treated as if they don't exist yet
+fn apply_release_age(index: NodeDistIndex, config: &NodePluginConfig) -> NodeDistIndex {
+    if config.min_release_age == 0 {
+        index
+    } else {
+        index.released_by(today() - i64::from(config.min_release_age))
+    }
+}
+
+fn get_version_reqs(spec: &UnresolvedVersionSpec) -> Vec<VersionReq> {
+    match spec {
+        UnresolvedVersionSpec::Req(req) => vec![req.to_owned()],
+        UnresolvedVersionSpec::ReqAny(reqs) => reqs.to_owned(),
+        _ => vec![],
+    }
+}
+
+// The environment variable takes precedence, so that it can be
+// toggled per invocation without changing configuration
+fn get_range_strategy() -> Result<NodeRangeStrategy, Error> {
+    match host_env!("PROTO_NODE_RANGE_STRATEGY").as_deref() {
+        Some("highest") => Ok(NodeRangeStrategy::Highest),
+        Some("lowest") => Ok(NodeRangeStrategy::Lowest),
+        Some(value) => Err(PluginError::Message(format!(
+            "Invalid PROTO_NODE_RANGE_STRATEGY value \"{value}\", expected \"highest\" or \"lowest\"."
+        ))
+        .into()),
+        None => Ok(get_tool_config::<NodePluginConfig>()?.range_strategy),
+    }
+}
+
+// Shared with the package manager plugins, which redirect globals
+// into the directory that's looked up here
+fn get_globals_mode(config: &NodePluginConfig) -> Result<NodeGlobalsMode, Error> {
+    NodeGlobalsMode::from_env_or(host_env!("PROTO_NODE_GLOBALS_MODE"), config.globals_mode)
+}
+
 #[plugin_fn]
 pub fn load_versions(Json(_): Json<LoadVersionsInput>) -> FnResult<Json<LoadVersionsOutput>> {
     let mut output = LoadVersionsOutput::default();
-    let response: Vec<NodeDistVersion> =
-        fetch_url("https://nodejs.org/download/release/index.json")?;
+    let config = get_tool_config::<NodePluginConfig>()?;
+    let full_index = load_dist_index(NODE_DIST_INDEX_URL, &config)?;
+    let index = apply_release_age(full_index.clone(), &config);
 
-    for (index, item) in response.iter().enumerate() {
-        let version = UnresolvedVersionSpec::parse(&item.version[1..])?;
+    for item in &index.versions {
+        output
+            .versions
+            .push(VersionSpec::parse(item.get_version())?);
+    }
 
-        // First item is always the latest
-        if index == 0 {
-            output.latest = Some(version.clone());
-        }
+    let Some(latest) = index.latest() else {
+        let message = if config.min_release_age > 0 {
+            format!(
+                "No {NAME} versions were released more than {} days ago, as required by min-release-age.",
+                config.min_release_age
+            )
+        } else {
+            format!("No {NAME} versions are available.")
+        };
 
-        if let NodeDistLTS::Name(alias) = &item.lts {
-            let alias = alias.to_lowercase();
+        return Err(PluginError::Message(message).into());
+    };
 
-            // The first encounter of an lts is the latest stable
-            if !output.aliases.contains_key("stable") {
-                output.aliases.insert("stable".into(), version.clone());
-            }
+    output.latest = Some(UnresolvedVersionSpec::parse(latest.get_version())?);
 
-            // The first encounter of an lts is the latest version for that alias
-            if !output.aliases.contains_key(&alias) {
-                output.aliases.insert(alias.clone(), version.clone());
+    if let Some(stable) = index.latest_lts() {
+        output.aliases.insert(
+            "stable".into(),
+            UnresolvedVersionSpec::parse(stable.get_version())?,
+        );
+    }
+
+    // Codenames are ordered from newest to oldest, so the position
+    // is the relative line (`lts/-1` is the line before the newest)
+    for (offset, codename) in index.lts_codenames().into_iter().enumerate() {
+        if let Some(item) = index.latest_for_lts(&codename) {
+            let version = UnresolvedVersionSpec::parse(item.get_version())?;
+
+            if offset > 0 {
+                output
+                    .aliases
+                    .insert(format!("lts--{offset}"), version.clone());
             }
+
+            output.aliases.insert(codename, version);
+        }
+    }
+
+    // Security aliases point to the newest release that contains security fixes,
+    // which may not be the newest release of the line if a non-security
+    // release came afterwards
+    if let Some(item) = index.latest_security() {
+        output.aliases.insert(
+            "security".into(),
+            UnresolvedVersionSpec::parse(item.get_version())?,
+        );
+    }
+
+    if let Some(item) = index
+        .latest_lts()
+        .and_then(|lts| lts.to_version())
+        .and_then(|lts| index.latest_security_in_major(lts.major))
+    {
+        output.aliases.insert(
+            "lts-security".into(),
+            UnresolvedVersionSpec::parse(item.get_version())?,
+        );
+    }
+
+    for major in index.majors() {
+        if let Some(item) = index.latest_security_in_major(major) {
+            output.aliases.insert(
+                format!("security-{major}"),
+                UnresolvedVersionSpec::parse(item.get_version())?,
+            );
         }
+    }
+
+    for abi in index.module_versions() {
+        if let Some(item) = index.latest_for_module_version(abi) {
+            output.aliases.insert(
+                format!("abi-{abi}"),
+                UnresolvedVersionSpec::parse(item.get_version())?,
+            );
+        }
+    }
 
-        output.versions.push(version.to_resolved_spec());
+    // Phase aliases depend on the release schedule, which shouldn't
+    // prevent versions from loading if it's unavailable
+    match load_release_schedule(&config) {
+        Ok(schedule) => {
+            let today = today();
+
+            for (alias, phase) in [
+                ("current", NodeReleasePhase::Current),
+                ("active-lts", NodeReleasePhase::ActiveLts),
+                ("maintenance-lts", NodeReleasePhase::MaintenanceLts),
+            ] {
+                let items = schedule
+                    .majors_in_phase(phase, today)
+                    .into_iter()
+                    .filter_map(|major| index.latest_in_major(major))
+                    .collect::<Vec<_>>();
+
+                // Multiple lines can be in maintenance at once, so older lines
+                // are relative to the newest (`maintenance-lts--1`), like `lts--N`
+                for (offset, item) in items.into_iter().enumerate() {
+                    let version = UnresolvedVersionSpec::parse(item.get_version())?;
+
+                    if offset == 0 {
+                        output.aliases.insert(alias.into(), version);
+                    } else if phase == NodeReleasePhase::MaintenanceLts {
+                        output.aliases.insert(format!("{alias}--{offset}"), version);
+                    } else {
+                        break;
+                    }
+                }
+            }
+        }
+        Err(error) => {
+            debug!("Unable to load {NAME} release schedule, skipping phase aliases: {error}");
+        }
     }
 
-    output
-        .aliases
-        .insert("latest".into(), output.latest.clone().unwrap());
+    output.aliases.insert(
+        "latest".into(),
+        UnresolvedVersionSpec::parse(latest.get_version())?,
+    );
+
+    // Let the user know when an alias is pinned to an older version
+    // because of the cooldown period
+    if config.min_release_age > 0 {
+        let mut newest = vec![
+            ("latest".to_owned(), full_index.latest()),
+            ("stable".to_owned(), full_index.latest_lts()),
+        ];
+
+        for codename in full_index.lts_codenames() {
+            let item = full_index.latest_for_lts(&codename);
+            newest.push((codename, item));
+        }
+
+        for (alias, item) in newest {
+            let Some(item) = item else {
+                continue;
+            };
+
+            let current = output.aliases.get(&alias).map(|spec| spec.to_string());
+
+            if current.as_deref() != Some(item.get_version()) {
+                host_log!(
+                    stderr,
+                    "Skipped {NAME} v{} for the \"{alias}\" alias, as it was released on {}, which is within the min-release-age of {} days (using {} instead)",
+                    item.get_version(),
+                    item.date,
+                    config.min_release_age,
+                    current.as_deref().unwrap_or("no version")
+                );
+            }
+        }
+    }
 
     Ok(Json(output))
 }
@@ -117,18 +343,58 @@ pub fn resolve_version(
 ) -> FnResult<Json<ResolveVersionOutput>> {
     let mut output = ResolveVersionOutput::default();
 
+    let reqs = get_version_reqs(&input.initial);
+
+    if !reqs.is_empty() {
+        let config = get_tool_config::<NodePluginConfig>()?;
+
+        if config.range_policy != NodeRangePolicy::Highest {
+            let index = apply_release_age(load_dist_index(NODE_DIST_INDEX_URL, &config)?, &config);
+
+            if let Some(item) = index.latest_lts_matching(&reqs) {
+                output.candidate = Some(UnresolvedVersionSpec::parse(item.get_version())?);
+            } else if config.range_policy == NodeRangePolicy::LtsOnly {
+                return Err(plugin_err!(PluginError::Message(format!(
+                    "No {NAME} LTS version satisfies {}. Set range-policy to \"prefer-lts\" to allow non-LTS versions.",
+                    input.initial
+                ))));
+            }
+        }
+
+        return Ok(Json(output));
+    }
+
     if let UnresolvedVersionSpec::Alias(alias) = input.initial {
+        // Installed by the OS, so use as-is, and locate it when installing
+        if alias == SYSTEM_ALIAS {
+            output.version = Some(VersionSpec::Alias(SYSTEM_ALIAS.into()));
+
+            return Ok(Json(output));
+        }
+
         let candidate = if alias == "node" {
-            "latest"
+            "latest".into()
         } else if alias == "lts" || alias == "lts-latest" || alias == "lts-*" || alias == "lts/*" {
-            "stable"
+            "stable".into()
+        } else if alias == "lts-security" || alias == "lts/security" {
+            "lts-security".into()
+        } else if let Some(offset) = alias
+            .strip_prefix("lts/-")
+            .or_else(|| alias.strip_prefix("lts--"))
+            .and_then(|offset| offset.parse::<usize>().ok())
+        {
+            if offset == 0 {
+                "stable".into()
+            } else {
+                format!("lts--{offset}")
+            }
         } else if alias.starts_with("lts-") || alias.starts_with("lts/") {
-            &alias[4..]
+            alias[4..].to_owned()
         } else {
             return Ok(Json(output));
         };
 
-        output.candidate = Some(UnresolvedVersionSpec::Alias(candidate.to_owned()));
+        output.candidate = Some(UnresolvedVersionSpec::Alias(candidate));
     }
 
     Ok(Json(output))
@@ -148,55 +414,148 @@ fn map_arch(os: HostOS, arch: HostArch) -> Result<String, PluginError> {
         HostArch::S390x => "s390x".into(),
         HostArch::X64 => "x64".into(),
         HostArch::X86 => "x86".into(),
-        _ => unreachable!(),
+        _ => {
+            return Err(PluginError::UnsupportedTarget {
+                tool: NAME.into(),
+                arch: arch.to_string(),
+                os: os.to_string(),
+            });
+        }
     };
 
     Ok(arch)
 }
 
+// These map to the `files` entries in the dist index
+fn map_dist_file(os: HostOS, arch: &str) -> Result<String, PluginError> {
+    let file = match os {
+        HostOS::Linux => format!("linux-{arch}"),
+        HostOS::MacOS => format!("osx-{arch}-tar"),
+        HostOS::Windows => format!("win-{arch}-zip"),
+        _ => {
+            return Err(PluginError::UnsupportedOS {
+                tool: NAME.into(),
+                os: os.to_string(),
+            });
+        }
+    };
+
+    Ok(file)
+}
+
+fn find_dist_file(files: &[String], os: HostOS, arch: &str) -> Result<Option<String>, PluginError> {
+    let file = map_dist_file(os, arch)?;
+
+    if files.contains(&file) {
+        return Ok(Some(file));
+    }
+
+    // Older Windows releases (v4.0-v4.4, v5, v6.0-v6.2) were not packaged
+    // as a zip, and only provide a standalone `node.exe` (and an msi)
+    if os == HostOS::Windows {
+        let exe_file = format!("win-{arch}-exe");
+
+        if files.contains(&exe_file) {
+            return Ok(Some(exe_file));
+        }
+    }
+
+    Ok(None)
+}
+
+// The index is located alongside the version directories, for example:
+// https://nodejs.org/download/release/v{version}/{file} -> https://nodejs.org/download/release/index.json
+fn get_dist_index_url(dist_url: &str) -> String {
+    match dist_url.split_once("v{version}") {
+        Some((base, _)) => format!("{base}index.json"),
+        None => NODE_DIST_INDEX_URL.into(),
+    }
+}
+
 #[plugin_fn]
 pub fn download_prebuilt(
     Json(input): Json<DownloadPrebuiltInput>,
 ) -> FnResult<Json<DownloadPrebuiltOutput>> {
     let env = get_host_environment()?;
-
-    check_supported_os_and_arch(
-        NAME,
-        &env,
-        permutations! [
-            HostOS::Linux => [HostArch::X64, HostArch::Arm64, HostArch::Arm, HostArch::Powerpc64, HostArch::S390x],
-            HostOS::MacOS => [HostArch::X64, HostArch::Arm64],
-            HostOS::Windows => [HostArch::X64, HostArch::X86, HostArch::Arm64],
-        ],
-    )?;
-
-    let arch = map_arch(env.os, env.arch)?;
+    let config = get_tool_config::<NodePluginConfig>()?;
     let mut version = input.context.version;
-    let mut host = get_tool_config::<NodePluginConfig>()?.dist_url;
+    let mut host = config.dist_url.clone();
 
     // When canary, extract the latest version from the index
-    if version.is_canary() {
-        let response: Vec<NodeDistVersion> =
-            fetch_url("https://nodejs.org/download/nightly/index.json")?;
-
+    let release = if version.is_canary() {
         host = host.replace("/release/", "/nightly/");
-        version = VersionSpec::parse(&response[0].version)?;
+
+        let mut index = load_dist_index(&get_dist_index_url(&host), &config)?;
+
+        version = VersionSpec::parse(&index.versions[0].version)?;
+
+        Some(index.versions.remove(0))
+    } else {
+        match load_dist_index(&get_dist_index_url(&host), &config) {
+            Ok(index) => index.find_version(&version.to_string()).cloned(),
+            Err(error) => {
+                debug!("Unable to load the dist index: {error}");
+
+                None
+            }
+        }
+    };
+
+    let target_arch = config.arch.unwrap_or(env.arch);
+    let mut arch = map_arch(env.os, target_arch)?;
+    let mut file = map_dist_file(env.os, &arch)?;
+
+    // Not every release provides an artifact for every platform, for example,
+    // macOS arm64 was added in v16 and Windows arm64 in v19.9. When not available,
+    // fallback to an x64 artifact that can be ran via Rosetta or Prism emulation.
+    if let Some(release) = &release {
+        let can_emulate = config.emulation_fallback
+            && target_arch == HostArch::Arm64
+            && matches!(env.os, HostOS::MacOS | HostOS::Windows);
+
+        if let Some(native_file) = find_dist_file(&release.files, env.os, &arch)? {
+            file = native_file;
+        } else if let Some(emulated_file) =
+            find_dist_file(&release.files, env.os, "x64")?.filter(|_| can_emulate)
+        {
+            debug!(
+                "{NAME} v{version} does not provide a {file} artifact, falling back to {emulated_file} (emulated)"
+            );
+
+            arch = "x64".into();
+            file = emulated_file;
+        } else {
+            return Err(plugin_err!(PluginError::Message(format!(
+                "Unable to install {NAME} v{version}, no {file} artifact is available. Available artifacts: {}",
+                release.files.join(", ")
+            ))));
+        }
+    } else {
+        debug!("Unable to find {NAME} v{version} in the dist index, assuming a {file} artifact is available");
+    }
+
+    debug!("Downloading {NAME} v{version} for the {arch} architecture");
+
+    let checksum_url = host
+        .replace("{version}", &version.to_string())
+        .replace("{file}", "SHASUMS256.txt");
+
+    // Standalone executables are not archived, and are located
+    // within a folder, for example: v5.12.0/win-x64/node.exe
+    if file.ends_with("-exe") {
+        return Ok(Json(DownloadPrebuiltOutput {
+            download_url: host
+                .replace("{version}", &version.to_string())
+                .replace("{file}", &format!("win-{arch}/{BIN}.exe")),
+            download_name: Some(format!("{BIN}.exe")),
+            checksum_url: Some(checksum_url),
+            ..DownloadPrebuiltOutput::default()
+        }));
     }
 
     let prefix = match env.os {
         HostOS::Linux => format!("node-v{version}-linux-{arch}"),
-        HostOS::MacOS => {
-            let m1_compat_version = Version::new(20, 0, 0);
-            let parsed_version = version.as_version().unwrap_or(&m1_compat_version);
-
-            // Arm64 support was added after v16, but M1/M2 machines can
-            // run x64 binaries via Rosetta. This is a compat hack!
-            if env.arch == HostArch::Arm64 && parsed_version.major < 16 {
-                format!("node-v{version}-darwin-x64")
-            } else {
-                format!("node-v{version}-darwin-{arch}")
-            }
-        }
+        HostOS::MacOS => format!("node-v{version}-darwin-{arch}"),
         HostOS::Windows => format!("node-v{version}-win-{arch}"),
         _ => unreachable!(),
     };
@@ -213,19 +572,395 @@ pub fn download_prebuilt(
             .replace("{version}", &version.to_string())
             .replace("{file}", &filename),
         download_name: Some(filename),
-        checksum_url: Some(
-            host.replace("{version}", &version.to_string())
-                .replace("{file}", "SHASUMS256.txt"),
-        ),
+        checksum_url: Some(checksum_url),
         ..DownloadPrebuiltOutput::default()
     }))
 }
 
+// Standalone executables are not unpacked from an archive, and are instead
+// renamed by proto to the plugin identifier, which may not be "node"
+fn get_windows_exe_name(tool_dir: &VirtualPath) -> Result<String, Error> {
+    let exe_name = format!("{}.exe", BIN);
+
+    if !tool_dir.join(&exe_name).exists() {
+        let id_exe_name = format!("{}.exe", get_plugin_id()?);
+
+        if tool_dir.join(&id_exe_name).exists() {
+            return Ok(id_exe_name);
+        }
+    }
+
+    Ok(exe_name)
+}
+
+fn get_node_path(tool_dir: &VirtualPath, os: HostOS) -> Result<VirtualPath, Error> {
+    Ok(if os == HostOS::Windows {
+        tool_dir.join(get_windows_exe_name(tool_dir)?)
+    } else {
+        tool_dir.join(format!("bin/{}", BIN))
+    })
+}
+
+// These map to the values of `process.arch`
+fn map_process_arch(arch: HostArch) -> &'static str {
+    match arch {
+        HostArch::Arm => "arm",
+        HostArch::Arm64 => "arm64",
+        HostArch::Powerpc64 => "ppc64",
+        HostArch::S390x => "s390x",
+        HostArch::X86 => "ia32",
+        _ => "x64",
+    }
+}
+
+// Emulated x64 binaries may be installed when a native arm64 artifact isn't available
+fn is_expected_process_arch(
+    process_arch: &str,
+    config: &NodePluginConfig,
+    env: &HostEnvironment,
+) -> bool {
+    let target_arch = config.arch.unwrap_or(env.arch);
+
+    process_arch == map_process_arch(target_arch)
+        || (config.emulation_fallback
+            && target_arch == HostArch::Arm64
+            && matches!(env.os, HostOS::MacOS | HostOS::Windows)
+            && process_arch == "x64")
+}
+
+const SYSTEM_ALIAS: &str = "system";
+
+// A Node.js binary that was installed by the OS, outside of proto
+#[derive(serde::Deserialize, serde::Serialize)]
+struct SystemNode {
+    path: String,
+    version: String,
+}
+
+fn is_system_version(version: &VersionSpec) -> bool {
+    matches!(version, VersionSpec::Alias(alias) if alias == SYSTEM_ALIAS)
+}
+
+// Find the first `node` on `PATH` that isn't a proto shim or binary,
+// by asking the host, as the sandbox can't access arbitrary paths
+fn find_system_node() -> Result<Option<SystemNode>, Error> {
+    let env = get_host_environment()?;
+    let proto_dir = real_path!(buf, PathBuf::from("/proto"));
+
+    let output = if env.os == HostOS::Windows {
+        exec_command!(pipe, "where", [BIN])
+    } else {
+        exec_command!(pipe, "which", ["-a", BIN])
+    };
+
+    for path in output.stdout.lines().map(|line| line.trim()) {
+        if path.is_empty() || Path::new(path).starts_with(&proto_dir) {
+            continue;
+        }
+
+        let output = exec_command!(pipe, path, ["--version"]);
+
+        if output.exit_code != 0 {
+            debug!("Unable to run {path}, skipping");
+
+            continue;
+        }
+
+        return Ok(Some(SystemNode {
+            path: path.to_owned(),
+            version: output.stdout.trim().trim_start_matches('v').to_owned(),
+        }));
+    }
+
+    Ok(None)
+}
+
+fn load_system_node(tool_dir: &VirtualPath) -> Result<SystemNode, Error> {
+    if let Ok(content) = fs::read_to_string(tool_dir.join("system.json")) {
+        return Ok(json::from_str(&content)?);
+    }
+
+    find_system_node()?.ok_or_else(|| {
+        PluginError::Message(format!(
+            "Unable to find a system {NAME} on PATH, outside of proto's directories."
+        ))
+        .into()
+    })
+}
+
+#[plugin_fn]
+pub fn native_install(
+    Json(input): Json<NativeInstallInput>,
+) -> FnResult<Json<NativeInstallOutput>> {
+    // Other versions are downloaded and unpacked by proto
+    if !is_system_version(&input.context.version) {
+        let config = get_tool_config::<NodePluginConfig>()?;
+        let imported = !config.import_from.is_empty() && import_install(&input, &config)?;
+
+        return Ok(Json(NativeInstallOutput {
+            installed: imported,
+            skip_install: !imported,
+            ..NativeInstallOutput::default()
+        }));
+    }
+
+    let system = load_system_node(&input.install_dir)?;
+
+    host_log!(
+        stdout,
+        "Using system {NAME} v{} from {}",
+        system.version,
+        system.path
+    );
+
+    fs::create_dir_all(&input.install_dir)?;
+    fs::write(
+        input.install_dir.join("system.json"),
+        json::to_string_pretty(&system)?,
+    )?;
+
+    Ok(Json(NativeInstallOutput {
+        installed: true,
+        ..NativeInstallOutput::default()
+    }))
+}
+
+// Return the directories where other version managers store the provided version
+fn get_import_dirs(
+    source: NodeImportSource,
+    version: &Version,
+    os: HostOS,
+) -> Result<Vec<PathBuf>, Error> {
+    let home = real_path!(buf, PathBuf::from("/userhome"));
+    let env_dir = |name: &str| -> Result<Option<PathBuf>, Error> {
+        Ok(host_env!(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from))
+    };
+
+    let dirs = match source {
+        NodeImportSource::Fnm => {
+            let mut bases = vec![];
+
+            if let Some(dir) = env_dir("FNM_DIR")? {
+                bases.push(dir);
+            } else if os == HostOS::Windows {
+                bases.extend(env_dir("APPDATA")?.map(|dir| dir.join("fnm")));
+            } else {
+                if os == HostOS::MacOS {
+                    bases.push(home.join("Library/Application Support/fnm"));
+                }
+
+                bases.push(
+                    env_dir("XDG_DATA_HOME")?
+                        .unwrap_or_else(|| home.join(".local/share"))
+                        .join("fnm"),
+                );
+                bases.push(home.join(".fnm"));
+            }
+
+            bases
+                .into_iter()
+                .map(|base| base.join(format!("node-versions/v{version}/installation")))
+                .collect()
+        }
+        NodeImportSource::N if os != HostOS::Windows => {
+            vec![env_dir("N_PREFIX")?
+                .unwrap_or_else(|| PathBuf::from("/usr/local"))
+                .join(format!("n/versions/node/{version}"))]
+        }
+        NodeImportSource::Nvm if os == HostOS::Windows => env_dir("NVM_HOME")?
+            .map(|dir| dir.join(format!("v{version}")))
+            .into_iter()
+            .collect(),
+        NodeImportSource::Nvm => {
+            vec![env_dir("NVM_DIR")?
+                .unwrap_or_else(|| home.join(".nvm"))
+                .join(format!("versions/node/v{version}"))]
+        }
+        NodeImportSource::Volta => {
+            let base = match env_dir("VOLTA_HOME")? {
+                Some(dir) => Some(dir),
+                None if os == HostOS::Windows => {
+                    env_dir("LOCALAPPDATA")?.map(|dir| dir.join("Volta"))
+                }
+                None => Some(home.join(".volta")),
+            };
+
+            base.map(|base| base.join(format!("tools/image/node/{version}")))
+                .into_iter()
+                .collect()
+        }
+        _ => vec![],
+    };
+
+    Ok(dirs)
+}
+
+// Validate the layout of an install, and return the version that its binary reports.
+// Other version managers may store installs outside of the sandbox, so on Unix,
+// the files are checked by the host instead.
+fn probe_install(dir: &Path, os: HostOS) -> Result<Option<(String, String)>, Error> {
+    let script = "process.versions.node + ' ' + process.arch";
+
+    let output = if os == HostOS::Windows {
+        let dir = virtual_path!(buf, dir);
+
+        if !dir.join("node.exe").exists() || !dir.join("node_modules/npm/package.json").exists() {
+            return Ok(None);
+        }
+
+        exec_command!(pipe, dir.join("node.exe").to_string(), ["-p", script])
+    } else {
+        exec_command!(
+            pipe,
+            "sh",
+            [
+                "-c",
+                "test -x \"$1/bin/node\" && test -f \"$1/lib/node_modules/npm/package.json\" && exec \"$1/bin/node\" -p \"$2\"",
+                "sh",
+                &dir.to_string_lossy(),
+                script,
+            ]
+        )
+    };
+
+    if output.exit_code != 0 {
+        return Ok(None);
+    }
+
+    Ok(output
+        .stdout
+        .trim()
+        .split_once(' ')
+        .map(|(version, arch)| (version.to_owned(), arch.to_owned())))
+}
+
+// Adopt a version that was installed by another version manager, instead of
+// downloading it again. Unix installs are copied by the host, so that symlinks
+// and permissions are preserved, which isn't possible within the sandbox.
+fn import_install(input: &NativeInstallInput, config: &NodePluginConfig) -> Result<bool, Error> {
+    let Some(version) = input.context.version.as_version() else {
+        return Ok(false);
+    };
+
+    // Other version managers name their directories after stable releases,
+    // so pre-release and build versions (like nightlies) are always downloaded
+    if !version.pre.is_empty() || !version.build.is_empty() {
+        return Ok(false);
+    }
+
+    let env = get_host_environment()?;
+
+    for source in &config.import_from {
+        for dir in get_import_dirs(*source, version, env.os)? {
+            let Some((reported_version, reported_arch)) = probe_install(&dir, env.os)? else {
+                continue;
+            };
+
+            if reported_version != version.to_string() {
+                debug!(
+                    "Unable to import {}, its binary reports version {reported_version}",
+                    dir.display()
+                );
+
+                continue;
+            }
+
+            if !is_expected_process_arch(&reported_arch, config, &env) {
+                debug!(
+                    "Unable to import {}, its binary reports the {reported_arch} architecture",
+                    dir.display()
+                );
+
+                continue;
+            }
+
+            host_log!(stdout, "Importing {NAME} v{version} from {}", dir.display());
+
+            fs::create_dir_all(&input.install_dir)?;
+
+            if env.os == HostOS::Windows {
+                copy_dir_all(&virtual_path!(buf, dir), &input.install_dir)?;
+            } else {
+                let install_dir = real_path!(buf, input.install_dir.to_path_buf());
+
+                let output = exec_command!(
+                    pipe,
+                    "cp",
+                    [
+                        "-R",
+                        &format!("{}/.", dir.display()),
+                        &install_dir.to_string_lossy(),
+                    ]
+                );
+
+                if output.exit_code != 0 {
+                    return Err(PluginError::Message(format!(
+                        "Failed to import {NAME} v{version} from {}: {}",
+                        dir.display(),
+                        output.stderr.trim()
+                    ))
+                    .into());
+                }
+            }
+
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
 #[plugin_fn]
 pub fn locate_executables(
-    Json(_): Json<LocateExecutablesInput>,
+    Json(input): Json<LocateExecutablesInput>,
 ) -> FnResult<Json<LocateExecutablesOutput>> {
     let env = get_host_environment()?;
+    let config = get_tool_config::<NodePluginConfig>()?;
+    let mut secondary = HashMap::default();
+
+    // These ship with Node.js, but not every version includes corepack
+    for (name, script) in [
+        ("npm", "node_modules/npm/bin/npm-cli.js"),
+        ("npx", "node_modules/npm/bin/npx-cli.js"),
+        ("corepack", "node_modules/corepack/dist/corepack.js"),
+    ] {
+        let (launcher, script) = if env.os == HostOS::Windows {
+            (format!("{name}.cmd"), script.to_owned())
+        } else {
+            (format!("bin/{name}"), format!("lib/{script}"))
+        };
+
+        if !input.context.tool_dir.join(launcher).exists() {
+            continue;
+        }
+
+        let mut exe = ExecutableConfig::with_parent(script, BIN);
+        exe.no_bin = true;
+        exe.no_shim = match name {
+            "corepack" => config.corepack == NodeCorepackMode::Off,
+            // The npm tool owns the npm and npx shims
+            _ => true,
+        };
+
+        secondary.insert(name.into(), exe);
+    }
+
+    let globals_lookup_dirs = vec![format!(
+        "$PROTO_HOME/tools/node/{}/bin",
+        get_globals_mode(&config)?.get_globals_dir(&input.context.version.to_string())
+    )];
+
+    if is_system_version(&input.context.version) {
+        return Ok(Json(LocateExecutablesOutput {
+            globals_lookup_dirs,
+            primary: Some(ExecutableConfig::new(
+                load_system_node(&input.context.tool_dir)?.path,
+            )),
+            ..LocateExecutablesOutput::default()
+        }));
+    }
 
     Ok(Json(LocateExecutablesOutput {
         exes_dir: Some(if env.os == HostOS::Windows {
@@ -233,21 +968,169 @@ pub fn locate_executables(
         } else {
             "bin".into()
         }),
-        globals_lookup_dirs: vec!["$PROTO_HOME/tools/node/globals/bin".into()],
+        globals_lookup_dirs,
         primary: Some(ExecutableConfig::new(if env.os == HostOS::Windows {
-            format!("{}.exe", BIN)
+            get_windows_exe_name(&input.context.tool_dir)?
         } else {
             format!("bin/{}", BIN)
         })),
+        secondary,
         ..LocateExecutablesOutput::default()
     }))
 }
 
-#[plugin_fn]
-pub fn post_install(Json(input): Json<InstallHook>) -> FnResult<()> {
-    let config = get_tool_config::<NodePluginConfig>()?;
-
-    if !config.bundled_npm
+fn get_corepack_path(os: HostOS) -> &'static str {
+    if os == HostOS::Windows {
+        "node_modules/corepack/dist/corepack.js"
+    } else {
+        "lib/node_modules/corepack/dist/corepack.js"
+    }
+}
+
+// Corepack shims are installed into a directory that's managed by proto,
+// so that they never overwrite the npm, pnpm, and yarn shims in `~/.proto/shims`
+fn enable_corepack(input: &InstallHook) -> Result<(), Error> {
+    let env = get_host_environment()?;
+    let tool_dir = &input.context.tool_dir;
+    let corepack_path = tool_dir.join(get_corepack_path(env.os));
+
+    if !corepack_path.exists() {
+        debug!(
+            "Corepack is not available for {NAME} v{}",
+            input.context.version
+        );
+
+        return Ok(());
+    }
+
+    let shims_dir = PathBuf::from("/proto/tools/node/corepack");
+
+    fs::create_dir_all(&shims_dir)?;
+
+    debug!("Enabling corepack in {}", shims_dir.display());
+
+    let node_path = get_node_path(tool_dir, env.os)?;
+
+    let output = exec_command!(
+        inherit,
+        node_path.to_string(),
+        [
+            real_path!(buf, corepack_path.to_path_buf())
+                .to_string_lossy()
+                .to_string(),
+            "enable".into(),
+            "--install-directory".into(),
+            real_path!(buf, shims_dir).to_string_lossy().to_string(),
+        ]
+    );
+
+    if output.exit_code != 0 {
+        return Err(PluginError::Message(format!(
+            "Failed to enable corepack for {NAME} v{} (exit code {})",
+            input.context.version, output.exit_code
+        ))
+        .into());
+    }
+
+    Ok(())
+}
+
+fn load_dist_index(url: &str, config: &NodePluginConfig) -> AnyResult<NodeDistIndex> {
+    node_common::load_dist_index(&Remote, url, config.index_cache_ttl)
+}
+
+// Cached like the dist index, and the cached copy is used while offline
+fn load_release_schedule(config: &NodePluginConfig) -> AnyResult<NodeReleaseSchedule> {
+    load_json_with_cache(
+        &Remote,
+        &config.schedule_url,
+        &get_release_schedule_cache_path(&config.schedule_url),
+        config.index_cache_ttl,
+    )
+}
+
+#[plugin_fn]
+pub fn pre_install(Json(input): Json<InstallHook>) -> FnResult<()> {
+    let config = get_tool_config::<NodePluginConfig>()?;
+
+    if config.eol_policy == NodeEolPolicy::Ignore {
+        return Ok(());
+    }
+
+    let Some(version) = input.context.version.as_version() else {
+        return Ok(());
+    };
+
+    // Only refuse to install when strict, otherwise an unreachable
+    // schedule shouldn't block the install
+    let schedule = match load_release_schedule(&config) {
+        Ok(schedule) => schedule,
+        Err(error) if config.eol_policy == NodeEolPolicy::Strict => return Err(error.into()),
+        Err(error) => {
+            debug!("Failed to load the release schedule, skipping end-of-life check: {error}");
+
+            return Ok(());
+        }
+    };
+
+    let Some(line) = schedule.find_line(version) else {
+        return Ok(());
+    };
+
+    let Some(days) = line.days_until_end(today()) else {
+        return Ok(());
+    };
+
+    if days <= 0 {
+        if config.eol_policy == NodeEolPolicy::Strict {
+            return Err(plugin_err!(PluginError::Message(format!(
+                "{NAME} v{version} reached end-of-life on {}, refusing to install. Set eol-policy to \"warn\" to allow it.",
+                line.end
+            ))));
+        }
+
+        host_log!(
+            stderr,
+            "{NAME} v{version} reached end-of-life on {}, and no longer receives security updates!",
+            line.end
+        );
+    } else if days <= i64::from(config.eol_warning_days) {
+        host_log!(
+            stderr,
+            "{NAME} v{version} will reach end-of-life on {} (in {days} days)",
+            line.end
+        );
+    }
+
+    Ok(())
+}
+
+#[plugin_fn]
+pub fn post_install(Json(input): Json<InstallHook>) -> FnResult<()> {
+    let config = get_tool_config::<NodePluginConfig>()?;
+
+    // The installation is managed by the OS
+    if is_system_version(&input.context.version) {
+        return Ok(());
+    }
+
+    if config.verify_install {
+        verify_install(&input, &config)?;
+    }
+
+    if config.corepack == NodeCorepackMode::Enable {
+        enable_corepack(&input)?;
+    }
+
+    if let Some(from) = input
+        .passthrough_args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("--reinstall-globals-from="))
+    {
+        reinstall_globals(&input, &config, from)?;
+    }
+
+    if !config.bundled_npm
         || input
             .passthrough_args
             .iter()
@@ -258,7 +1141,31 @@ pub fn post_install(Json(input): Json<InstallHook>) -> FnResult<()> {
 
     debug!("Installing npm that comes bundled with Node.js");
 
-    let mut args = vec!["install", "npm", "bundled"];
+    let npm_version = get_bundled_npm_version(&input.context.tool_dir)?;
+
+    // Only track the npm version if it's installed by this hook,
+    // so that it can be removed when this Node.js version is uninstalled
+    let bundled_version = npm_version
+        .clone()
+        .filter(|version| !PathBuf::from("/proto/tools/npm").join(version).exists());
+
+    // When copying, install the exact bundled version, and the npm plugin
+    // will copy it from this installation instead of downloading it
+    let copied_version = if config.bundled_npm_mode == NodeBundledNpmMode::Copy {
+        if npm_version.is_none() {
+            debug!("Unable to find the bundled npm package, falling back to a download");
+        }
+
+        npm_version
+    } else {
+        None
+    };
+
+    let mut args = vec![
+        "install",
+        "npm",
+        copied_version.as_deref().unwrap_or("bundled"),
+    ];
 
     if input.pinned {
         args.push("--pin");
@@ -269,7 +1176,7 @@ pub fn post_install(Json(input): Json<InstallHook>) -> FnResult<()> {
         .passthrough_args
         .iter()
         .filter_map(|arg| {
-            if arg.as_str() == "--no-bundled-npm" {
+            if arg.as_str() == "--no-bundled-npm" || arg.starts_with("--reinstall-globals-from=") {
                 None
             } else {
                 Some(arg.as_str())
@@ -282,7 +1189,645 @@ pub fn post_install(Json(input): Json<InstallHook>) -> FnResult<()> {
         args.extend(passthrough_args);
     }
 
-    exec_command!(inherit, "proto", args);
+    let mut command = ExecCommandInput::inherit("proto", args);
+
+    if copied_version.is_some() {
+        let env = get_host_environment()?;
+        let npm_dir = get_node_modules_dir(&input.context.tool_dir, env.os).join("npm");
+
+        command.env.insert(
+            "PROTO_NODE_BUNDLED_NPM_DIR".into(),
+            real_path!(buf, npm_dir.to_path_buf())
+                .to_string_lossy()
+                .to_string(),
+        );
+    }
+
+    exec_command!(input, command);
+
+    if let Some(npm_version) = bundled_version {
+        let mut state = load_bundled_npm_state()?;
+
+        state.insert(input.context.version.to_string(), npm_version);
+
+        save_bundled_npm_state(&state)?;
+    }
+
+    Ok(())
+}
+
+// Run the installed binary, to catch binaries that were extracted but can't run,
+// for example, when built for a different libc, or truncated by a proxy
+fn verify_install(input: &InstallHook, config: &NodePluginConfig) -> Result<(), Error> {
+    let env = get_host_environment()?;
+    let version = &input.context.version;
+    let node_path = get_node_path(&input.context.tool_dir, env.os)?;
+
+    let fail = |reason: String| -> Error {
+        PluginError::Message(format!(
+            "Failed to verify the {NAME} v{version} installation, {reason}. The download may be corrupted, or built for a different platform."
+        ))
+        .into()
+    };
+
+    if !node_path.exists() {
+        return Err(fail(format!("{} does not exist", node_path.display())));
+    }
+
+    let output = exec_command!(pipe, node_path.to_string(), ["--version"]);
+
+    if output.exit_code != 0 {
+        return Err(fail(format!(
+            "`{BIN} --version` failed with exit code {}: {}",
+            output.exit_code,
+            output.stderr.trim()
+        )));
+    }
+
+    let reported_version = output.stdout.trim().trim_start_matches('v').to_owned();
+
+    let output = exec_command!(
+        pipe,
+        node_path.to_string(),
+        ["-p", "process.versions.node + ' ' + process.arch"]
+    );
+
+    if output.exit_code != 0 {
+        return Err(fail(format!(
+            "`{BIN} -p process.versions` failed with exit code {}: {}",
+            output.exit_code,
+            output.stderr.trim()
+        )));
+    }
+
+    let (process_version, process_arch) = output.stdout.trim().split_once(' ').unwrap_or_default();
+
+    if process_version != reported_version {
+        return Err(fail(format!(
+            "`{BIN} --version` reported {reported_version}, but process.versions.node is {process_version}"
+        )));
+    }
+
+    // Ignore pre-release and build identifiers, like the architecture suffix
+    if let Some(expected) = version.as_version() {
+        let matches = Version::parse(process_version).is_ok_and(|actual| {
+            actual.major == expected.major
+                && actual.minor == expected.minor
+                && actual.patch == expected.patch
+        });
+
+        if !matches {
+            return Err(fail(format!(
+                "the binary reports version {process_version}"
+            )));
+        }
+    }
+
+    if !is_expected_process_arch(process_arch, config, &env) {
+        return Err(fail(format!(
+            "the binary reports the {process_arch} architecture, expected {}",
+            map_process_arch(config.arch.unwrap_or(env.arch))
+        )));
+    }
+
+    debug!("Verified {NAME} v{process_version} ({process_arch}) can run");
+
+    Ok(())
+}
+
+fn get_node_modules_dir(tool_dir: &Path, os: HostOS) -> PathBuf {
+    if os == HostOS::Windows {
+        tool_dir.join("node_modules")
+    } else {
+        tool_dir.join("lib/node_modules")
+    }
+}
+
+fn get_bundled_npm_version(tool_dir: &VirtualPath) -> Result<Option<String>, Error> {
+    let env = get_host_environment()?;
+    let package_path = get_node_modules_dir(tool_dir, env.os).join("npm/package.json");
+
+    let Ok(package_json) = fs::read_to_string(package_path) else {
+        return Ok(None);
+    };
+
+    Ok(json::from_str::<PackageJson>(&package_json)?.version)
+}
+
+// Tracks the npm versions that were installed by `post_install`, keyed by Node.js version
+fn get_bundled_npm_state_path() -> PathBuf {
+    PathBuf::from("/proto/tools/node/bundled-npm.json")
+}
+
+fn load_bundled_npm_state() -> Result<BTreeMap<String, String>, Error> {
+    let state_path = get_bundled_npm_state_path();
+
+    if !state_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    Ok(json::from_str(&fs::read_to_string(state_path)?)?)
+}
+
+fn save_bundled_npm_state(state: &BTreeMap<String, String>) -> Result<(), Error> {
+    let state_path = get_bundled_npm_state_path();
+
+    if let Some(parent) = state_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(state_path, json::to_string_pretty(state)?)?;
+
+    Ok(())
+}
+
+fn get_installed_tool_dir() -> Result<PathBuf, Error> {
+    Ok(PathBuf::from(format!("/proto/tools/{}", get_plugin_id()?)))
+}
+
+fn get_installed_versions() -> Result<Vec<Version>, Error> {
+    let tool_dir = get_installed_tool_dir()?;
+    let mut versions = vec![];
+
+    if !tool_dir.exists() {
+        return Ok(versions);
+    }
+
+    for entry in fs::read_dir(tool_dir)? {
+        if let Ok(version) = Version::parse(&entry?.file_name().to_string_lossy()) {
+            versions.push(version);
+        }
+    }
+
+    Ok(versions)
+}
+
+// Find the highest installed version that matches the provided version or requirement
+fn find_installed_version(from: &str) -> Result<Option<Version>, Error> {
+    let spec = UnresolvedVersionSpec::parse(from)?;
+
+    Ok(get_installed_versions()?
+        .into_iter()
+        .filter(|version| match &spec {
+            UnresolvedVersionSpec::Semantic(semver) => *version == semver.0,
+            UnresolvedVersionSpec::Req(req) => req.matches(version),
+            UnresolvedVersionSpec::ReqAny(reqs) => reqs.iter().any(|req| req.matches(version)),
+            _ => false,
+        })
+        .max())
+}
+
+// List the global packages in a `node_modules` directory, excluding
+// the packages that ship with Node.js
+fn list_global_packages(modules_dir: &Path) -> Result<Vec<String>, Error> {
+    let mut package_dirs = vec![];
+
+    if !modules_dir.exists() {
+        return Ok(vec![]);
+    }
+
+    for entry in fs::read_dir(modules_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        if name.starts_with('.') || name == "npm" || name == "corepack" {
+            continue;
+        }
+
+        if name.starts_with('@') {
+            for scoped in fs::read_dir(entry.path())? {
+                package_dirs.push(scoped?.path());
+            }
+        } else {
+            package_dirs.push(entry.path());
+        }
+    }
+
+    let mut packages = vec![];
+
+    for package_dir in package_dirs {
+        let Ok(content) = fs::read_to_string(package_dir.join("package.json")) else {
+            continue;
+        };
+        let package_json: PackageJson = json::from_str(&content)?;
+
+        if let Some(name) = package_json.name {
+            packages.push(match package_json.version {
+                Some(version) => format!("{name}@{version}"),
+                None => name,
+            });
+        }
+    }
+
+    packages.sort();
+
+    Ok(packages)
+}
+
+// Global packages are installed into the Node.js installation by default, or into
+// a globals directory when redirected by the package managers (`globals-mode` or
+// `shared-globals-dir`), so list them from the previous version, and install them
+// into the same location with the npm that comes bundled with the new version
+fn reinstall_globals(
+    input: &InstallHook,
+    config: &NodePluginConfig,
+    from: &str,
+) -> Result<(), Error> {
+    let env = get_host_environment()?;
+
+    let Some(from_version) = find_installed_version(from)? else {
+        host_log!(
+            stderr,
+            "Unable to reinstall global packages, {NAME} {from} is not installed"
+        );
+
+        return Ok(());
+    };
+
+    let from_dir = get_installed_tool_dir()?.join(from_version.to_string());
+    let globals_mode = get_globals_mode(config)?;
+    let get_globals_dir = |version: &str| {
+        let dir = PathBuf::from("/proto/tools/node").join(globals_mode.get_globals_dir(version));
+
+        // Matches the npm prefix that the package managers set
+        if env.os == HostOS::Windows {
+            dir.join("bin")
+        } else {
+            dir
+        }
+    };
+    let from_globals_dir = get_globals_dir(&from_version.to_string());
+    let to_globals_dir = get_globals_dir(&input.context.version.to_string());
+
+    let mut reinstalls = vec![];
+
+    for (modules_dir, prefix) in [
+        (get_node_modules_dir(&from_dir, env.os), None),
+        (
+            get_node_modules_dir(&from_globals_dir, env.os),
+            Some(to_globals_dir),
+        ),
+    ] {
+        let packages = list_global_packages(&modules_dir)?;
+
+        if packages.is_empty() {
+            debug!(
+                "No global packages to reinstall from {}",
+                modules_dir.display()
+            );
+        } else {
+            reinstalls.push((packages, prefix));
+        }
+    }
+
+    if reinstalls.is_empty() {
+        return Ok(());
+    }
+
+    let tool_dir = &input.context.tool_dir;
+    let npm_cli = get_node_modules_dir(tool_dir, env.os).join("npm/bin/npm-cli.js");
+
+    if !npm_cli.exists() {
+        host_log!(
+            stderr,
+            "Unable to reinstall global packages, npm is not bundled with {NAME} v{}",
+            input.context.version
+        );
+
+        return Ok(());
+    }
+
+    let node_path = get_node_path(tool_dir, env.os)?;
+    let npm_cli = real_path!(buf, npm_cli).to_string_lossy().to_string();
+
+    for (packages, prefix) in reinstalls {
+        debug!("Reinstalling global packages: {}", packages.join(", "));
+
+        let mut args = vec![npm_cli.clone(), "install".into(), "--global".into()];
+        args.extend(packages);
+
+        let mut command = ExecCommandInput::inherit(node_path.to_string(), args);
+
+        if let Some(prefix) = prefix {
+            command.env.insert(
+                "PREFIX".into(),
+                real_path!(buf, prefix).to_string_lossy().to_string(),
+            );
+        }
+
+        let output = exec_command!(input, command);
+
+        if output.exit_code != 0 {
+            host_log!(
+                stderr,
+                "Failed to reinstall global packages, npm exited with code {}",
+                output.exit_code
+            );
+        }
+    }
+
+    Ok(())
+}
+
+// Flags that were added after v12, and the version that introduced them.
+// Older versions refuse to start when `NODE_OPTIONS` contains an unknown flag.
+const NODE_OPTION_MIN_VERSIONS: &[(&str, &str)] = &[
+    ("--disable-warning", "21.3.0"),
+    ("--enable-source-maps", "12.12.0"),
+    ("--env-file", "20.6.0"),
+    ("--env-file-if-exists", "22.9.0"),
+    ("--experimental-default-type", "21.0.0"),
+    ("--experimental-detect-module", "21.1.0"),
+    ("--experimental-require-module", "22.0.0"),
+    ("--experimental-strip-types", "22.6.0"),
+    ("--experimental-transform-types", "22.7.0"),
+    ("--import", "20.0.0"),
+    ("--openssl-legacy-provider", "17.0.0"),
+    ("--trace-uncaught", "13.1.0"),
+];
+
+fn get_option_flag(option: &str) -> &str {
+    option.split_once('=').map_or(option, |(flag, _)| flag)
+}
+
+fn is_option_supported(option: &str, version: &Version) -> bool {
+    let flag = get_option_flag(option);
+    let flag = flag
+        .strip_prefix("--no-")
+        .map(|name| format!("--{name}"))
+        .unwrap_or_else(|| flag.to_owned());
+
+    NODE_OPTION_MIN_VERSIONS
+        .iter()
+        .find(|(name, _)| *name == flag)
+        .map_or(true, |(_, min)| {
+            Version::parse(min).is_ok_and(|min| *version >= min)
+        })
+}
+
+#[plugin_fn]
+pub fn pre_run(Json(input): Json<RunHook>) -> FnResult<Json<RunHookResult>> {
+    let config = get_tool_config::<NodePluginConfig>()?;
+    let mut result = RunHookResult::default();
+
+    if !config.run_env.is_empty() {
+        result
+            .env
+            .get_or_insert(HashMap::default())
+            .extend(config.run_env.clone());
+    }
+
+    if !config.node_options.is_empty() {
+        let user_options = host_env!("NODE_OPTIONS").unwrap_or_default();
+        let user_flags = user_options
+            .split_whitespace()
+            .map(get_option_flag)
+            .collect::<Vec<_>>();
+        let mut options = vec![];
+
+        for option in &config.node_options {
+            // Options that were set by the user take precedence
+            if user_flags.contains(&get_option_flag(option)) {
+                continue;
+            }
+
+            if let Some(version) = input.context.version.as_version() {
+                if !is_option_supported(option, version) {
+                    debug!(
+                        "Skipping node option {option} as it's not supported by {NAME} v{version}"
+                    );
+
+                    continue;
+                }
+            }
+
+            // Values with spaces must be quoted
+            options.push(if option.contains(char::is_whitespace) {
+                format!("\"{option}\"")
+            } else {
+                option.to_owned()
+            });
+        }
+
+        if !options.is_empty() {
+            if !user_options.trim().is_empty() {
+                options.insert(0, user_options.trim().to_owned());
+            }
+
+            result
+                .env
+                .get_or_insert(HashMap::default())
+                .insert("NODE_OPTIONS".into(), options.join(" "));
+        }
+    }
+
+    if config.compile_cache {
+        if let Some(cache_dir) = get_compile_cache_dir(&input.context.version)? {
+            result
+                .env
+                .get_or_insert(HashMap::default())
+                .entry("NODE_COMPILE_CACHE".into())
+                .or_insert(cache_dir);
+        }
+    }
+
+    // Only a warning, so it should never prevent Node.js from running
+    if input.context.version.as_version().is_some() {
+        if let Err(error) = check_module_version(&input) {
+            debug!("Failed to check the native addon ABI: {error}");
+        }
+    }
+
+    Ok(Json(result))
+}
+
+fn get_compile_cache_path(version: &VersionSpec) -> PathBuf {
+    PathBuf::from("/proto/tools/node/compile-cache").join(version.to_string())
+}
+
+// The module compile cache was added in v22.1, and is isolated per version,
+// as the cache is invalidated whenever the version changes
+fn get_compile_cache_dir(version: &VersionSpec) -> Result<Option<String>, Error> {
+    let supported = version
+        .as_version()
+        .is_some_and(|version| *version >= Version::new(22, 1, 0));
+
+    if !supported || host_env!("NODE_COMPILE_CACHE").is_some() {
+        return Ok(None);
+    }
+
+    let cache_dir = get_compile_cache_path(version);
+
+    fs::create_dir_all(&cache_dir)?;
+
+    Ok(Some(
+        real_path!(buf, cache_dir).to_string_lossy().to_string(),
+    ))
+}
+
+// The ABI is recorded by the package manager plugins when dependencies are installed
+fn check_module_version(input: &RunHook) -> AnyResult<()> {
+    if !PathBuf::from("/cwd/node_modules").exists() {
+        return Ok(());
+    }
+
+    let project_dir = real_path!(buf, PathBuf::from("/cwd"));
+
+    let Some(recorded_abi) = load_recorded_module_version(&project_dir.to_string_lossy())? else {
+        return Ok(());
+    };
+
+    let config = get_tool_config::<NodePluginConfig>()?;
+
+    let Some(abi) = get_module_version(
+        &Remote,
+        &input.context.tool_dir,
+        &input.context.version.to_string(),
+        config.index_cache_ttl,
+    )?
+    else {
+        return Ok(());
+    };
+
+    if recorded_abi != abi {
+        host_log!(
+            stderr,
+            "{NAME} v{} uses ABI {abi} (NODE_MODULE_VERSION), but node_modules was installed with ABI {recorded_abi}. Native addons may fail to load, reinstall dependencies to rebuild them.",
+            input.context.version,
+        );
+    }
+
+    Ok(())
+}
+
+// proto doesn't provide uninstall hooks, so clean up state that's tied
+// to this version before proto removes the install directory
+#[plugin_fn]
+pub fn native_uninstall(
+    Json(input): Json<NativeUninstallInput>,
+) -> FnResult<Json<NativeUninstallOutput>> {
+    let version = &input.context.version;
+    let globals_dir = PathBuf::from("/proto/tools/node/globals");
+
+    remove_version_dir(get_compile_cache_path(version), "compile cache")?;
+    remove_version_dir(globals_dir.join(version.to_string()), "globals directory")?;
+
+    if let Some(semver) = version.as_version() {
+        let other_in_major = get_installed_versions()?
+            .iter()
+            .any(|other| other.major == semver.major && other != semver);
+
+        if !other_in_major {
+            remove_version_dir(
+                globals_dir.join(semver.major.to_string()),
+                "globals directory",
+            )?;
+        }
+    }
+
+    uninstall_bundled_npm(&version.to_string())?;
+
+    // The install directory is removed by proto
+    Ok(Json(NativeUninstallOutput {
+        uninstalled: true,
+        ..NativeUninstallOutput::default()
+    }))
+}
+
+fn remove_version_dir(dir: PathBuf, label: &str) -> Result<(), Error> {
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+
+        host_log!(stdout, "Removed {label} {}", real_path!(buf, dir).display());
+    }
+
+    Ok(())
+}
+
+// Only the global, home, and current directory configs can be read, so a pin
+// in a parent directory isn't detected. To stay on the safe side, anything
+// that can't be ruled out (ranges, aliases, unparseable configs, etc) is
+// treated as a match, as it may resolve to the bundled version.
+fn is_npm_pinned(npm_version: &str) -> Result<bool, Error> {
+    if let Some(value) = host_env!("PROTO_NPM_VERSION") {
+        if is_npm_pin_match(&value, npm_version) {
+            return Ok(true);
+        }
+    }
+
+    for config_path in [
+        "/proto/.prototools",
+        "/userhome/.prototools",
+        "/cwd/.prototools",
+    ] {
+        let Ok(content) = fs::read_to_string(config_path) else {
+            continue;
+        };
+
+        let Ok(config) = toml::from_str::<toml::Table>(&content) else {
+            debug!("Unable to parse {config_path}, assuming npm is pinned");
+
+            return Ok(true);
+        };
+
+        // Versions are pinned at the top-level, not within tables like `[plugins]`
+        match config.get("npm") {
+            Some(toml::Value::String(value)) => {
+                if is_npm_pin_match(value, npm_version) {
+                    return Ok(true);
+                }
+            }
+            Some(_) => return Ok(true),
+            None => {}
+        };
+    }
+
+    Ok(false)
+}
+
+fn is_npm_pin_match(value: &str, npm_version: &str) -> bool {
+    match VersionSpec::parse(value) {
+        Ok(VersionSpec::Semantic(version)) => version.to_string() == npm_version,
+        _ => true,
+    }
+}
+
+// Only uninstall npm if it was installed by `post_install`,
+// and is not used by another Node.js version or pinned
+fn uninstall_bundled_npm(node_version: &str) -> Result<(), Error> {
+    let mut state = load_bundled_npm_state()?;
+
+    let Some(npm_version) = state.remove(node_version) else {
+        return Ok(());
+    };
+
+    if state.values().any(|version| *version == npm_version) || is_npm_pinned(&npm_version)? {
+        save_bundled_npm_state(&state)?;
+
+        host_log!(
+            stdout,
+            "Keeping bundled npm v{npm_version}, as it's still in use"
+        );
+
+        return Ok(());
+    }
+
+    let output = exec_command!(inherit, "proto", ["uninstall", "npm", &npm_version]);
+
+    // Keep tracking the version, so that it can be removed on a later attempt
+    if output.exit_code != 0 {
+        host_log!(
+            stderr,
+            "Failed to remove bundled npm v{npm_version} (exit code {})",
+            output.exit_code
+        );
+
+        return Ok(());
+    }
+
+    save_bundled_npm_state(&state)?;
+
+    host_log!(stdout, "Removed bundled npm v{npm_version}");
 
     Ok(())
 }
@@ -1,4 +1,9 @@
+mod utils;
+
+use node_common::{NodeCorepackMode, NodeGlobalsMode, NodePluginConfig};
 use proto_pdk_test_utils::*;
+use serial_test::serial;
+use std::env;
 
 generate_download_install_tests!("node-test", "18.0.0");
 
@@ -273,6 +278,207 @@ fn supports_windows_x86() {
     );
 }
 
+#[test]
+fn fallsback_to_x64_for_macos_arm64_without_artifact() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::MacOS, HostArch::Arm64);
+    });
+
+    assert_eq!(
+        plugin.download_prebuilt(DownloadPrebuiltInput {
+            context: ToolContext {
+                version: VersionSpec::parse("15.0.0").unwrap(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        DownloadPrebuiltOutput {
+            archive_prefix: Some("node-v15.0.0-darwin-x64".into()),
+            checksum_url: Some("https://nodejs.org/download/release/v15.0.0/SHASUMS256.txt".into()),
+            download_name: Some("node-v15.0.0-darwin-x64.tar.xz".into()),
+            download_url:
+                "https://nodejs.org/download/release/v15.0.0/node-v15.0.0-darwin-x64.tar.xz".into(),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn fallsback_to_x64_for_windows_arm64_without_artifact() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::Windows, HostArch::Arm64);
+    });
+
+    assert_eq!(
+        plugin.download_prebuilt(DownloadPrebuiltInput {
+            context: ToolContext {
+                version: VersionSpec::parse("18.0.0").unwrap(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        DownloadPrebuiltOutput {
+            archive_prefix: Some("node-v18.0.0-win-x64".into()),
+            checksum_url: Some("https://nodejs.org/download/release/v18.0.0/SHASUMS256.txt".into()),
+            download_name: Some("node-v18.0.0-win-x64.zip".into()),
+            download_url: "https://nodejs.org/download/release/v18.0.0/node-v18.0.0-win-x64.zip"
+                .into(),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+#[should_panic(expected = "no osx-arm64-tar artifact is available")]
+fn errors_without_artifact_when_emulation_disabled() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::MacOS, HostArch::Arm64);
+        config.tool_config(NodePluginConfig {
+            emulation_fallback: false,
+            ..Default::default()
+        });
+    });
+
+    plugin.download_prebuilt(DownloadPrebuiltInput {
+        context: ToolContext {
+            version: VersionSpec::parse("15.0.0").unwrap(),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+}
+
+#[test]
+#[should_panic(expected = "no win-x86-zip artifact is available")]
+fn errors_without_artifact_for_unsupported_platform() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::Windows, HostArch::X86);
+    });
+
+    // 32-bit Windows builds were dropped in v23
+    plugin.download_prebuilt(DownloadPrebuiltInput {
+        context: ToolContext {
+            version: VersionSpec::parse("23.0.0").unwrap(),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+}
+
+#[test]
+fn supports_windows_legacy_exe() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::Windows, HostArch::X64);
+    });
+
+    assert_eq!(
+        plugin.download_prebuilt(DownloadPrebuiltInput {
+            context: ToolContext {
+                version: VersionSpec::parse("5.12.0").unwrap(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        DownloadPrebuiltOutput {
+            checksum_url: Some("https://nodejs.org/download/release/v5.12.0/SHASUMS256.txt".into()),
+            download_name: Some("node.exe".into()),
+            download_url: "https://nodejs.org/download/release/v5.12.0/win-x64/node.exe".into(),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn supports_windows_legacy_exe_x86() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::Windows, HostArch::X86);
+    });
+
+    assert_eq!(
+        plugin.download_prebuilt(DownloadPrebuiltInput {
+            context: ToolContext {
+                version: VersionSpec::parse("4.0.0").unwrap(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        DownloadPrebuiltOutput {
+            checksum_url: Some("https://nodejs.org/download/release/v4.0.0/SHASUMS256.txt".into()),
+            download_name: Some("node.exe".into()),
+            download_url: "https://nodejs.org/download/release/v4.0.0/win-x86/node.exe".into(),
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn assumes_artifact_exists_when_index_unavailable() {
+    let dist_host = utils::start_unavailable_server();
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::Linux, HostArch::X64);
+        config.tool_config(NodePluginConfig {
+            dist_url: format!("{dist_host}/v{{version}}/{{file}}"),
+            ..Default::default()
+        });
+    });
+
+    assert_eq!(
+        plugin.download_prebuilt(DownloadPrebuiltInput {
+            context: ToolContext {
+                version: VersionSpec::parse("20.0.0").unwrap(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        DownloadPrebuiltOutput {
+            archive_prefix: Some("node-v20.0.0-linux-x64".into()),
+            checksum_url: Some(format!("{dist_host}/v20.0.0/SHASUMS256.txt")),
+            download_name: Some("node-v20.0.0-linux-x64.tar.xz".into()),
+            download_url: format!("{dist_host}/v20.0.0/node-v20.0.0-linux-x64.tar.xz"),
+            ..Default::default()
+        }
+    );
+
+    assert!(!sandbox.path().join(".proto/tools/node/cache").exists());
+}
+
+#[test]
+fn can_override_arch() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::MacOS, HostArch::Arm64);
+        config.tool_config(NodePluginConfig {
+            arch: Some(HostArch::X64),
+            ..Default::default()
+        });
+    });
+
+    assert_eq!(
+        plugin.download_prebuilt(DownloadPrebuiltInput {
+            context: ToolContext {
+                version: VersionSpec::parse("20.0.0").unwrap(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }),
+        DownloadPrebuiltOutput {
+            archive_prefix: Some("node-v20.0.0-darwin-x64".into()),
+            checksum_url: Some("https://nodejs.org/download/release/v20.0.0/SHASUMS256.txt".into()),
+            download_name: Some("node-v20.0.0-darwin-x64.tar.xz".into()),
+            download_url:
+                "https://nodejs.org/download/release/v20.0.0/node-v20.0.0-darwin-x64.tar.xz".into(),
+            ..Default::default()
+        }
+    );
+}
+
 #[test]
 fn locates_unix_bin() {
     let sandbox = create_empty_proto_sandbox();
@@ -316,3 +522,196 @@ fn locates_windows_bin() {
         Some("node.exe".into())
     );
 }
+
+#[test]
+fn locates_windows_legacy_bin() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::Windows, HostArch::X64);
+    });
+
+    // Standalone executables are renamed to the plugin ID by proto
+    sandbox.create_file(".proto/tools/node-test/5.12.0/node-test.exe", "");
+
+    assert_eq!(
+        plugin
+            .locate_executables(LocateExecutablesInput {
+                context: ToolContext {
+                    version: VersionSpec::parse("5.12.0").unwrap(),
+                    tool_dir: VirtualPath::OnlyReal(
+                        sandbox.path().join(".proto/tools/node-test/5.12.0")
+                    ),
+                    ..Default::default()
+                },
+            })
+            .primary
+            .unwrap()
+            .exe_path,
+        Some("node-test.exe".into())
+    );
+}
+
+fn locate_bundled_executables(
+    os: HostOS,
+    corepack: NodeCorepackMode,
+    launchers: &[&str],
+) -> LocateExecutablesOutput {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(os, HostArch::X64);
+        config.tool_config(NodePluginConfig {
+            corepack,
+            ..Default::default()
+        });
+    });
+
+    for launcher in launchers {
+        sandbox.create_file(format!(".proto/tools/node-test/20.0.0/{launcher}"), "");
+    }
+
+    plugin.locate_executables(LocateExecutablesInput {
+        context: ToolContext {
+            version: VersionSpec::parse("20.0.0").unwrap(),
+            tool_dir: VirtualPath::OnlyReal(sandbox.path().join(".proto/tools/node-test/20.0.0")),
+            ..Default::default()
+        },
+    })
+}
+
+#[test]
+fn locates_bundled_unix_executables() {
+    let output = locate_bundled_executables(
+        HostOS::Linux,
+        NodeCorepackMode::Off,
+        &["bin/npm", "bin/npx"],
+    );
+
+    let npx = output.secondary.get("npx").unwrap();
+
+    assert_eq!(
+        npx.exe_path,
+        Some("lib/node_modules/npm/bin/npx-cli.js".into())
+    );
+    assert_eq!(npx.parent_exe_name, Some("node".into()));
+    assert!(npx.no_bin);
+    assert!(npx.no_shim);
+    assert!(output.secondary.contains_key("npm"));
+    assert!(!output.secondary.contains_key("corepack"));
+}
+
+#[test]
+fn locates_bundled_windows_executables() {
+    let output = locate_bundled_executables(
+        HostOS::Windows,
+        NodeCorepackMode::Off,
+        &["npm.cmd", "npx.cmd", "corepack.cmd"],
+    );
+
+    assert_eq!(
+        output.secondary.get("npm").unwrap().exe_path,
+        Some("node_modules/npm/bin/npm-cli.js".into())
+    );
+    assert_eq!(
+        output.secondary.get("npx").unwrap().exe_path,
+        Some("node_modules/npm/bin/npx-cli.js".into())
+    );
+    assert_eq!(
+        output.secondary.get("corepack").unwrap().exe_path,
+        Some("node_modules/corepack/dist/corepack.js".into())
+    );
+}
+
+#[test]
+fn doesnt_shim_bundled_npm_or_npx() {
+    let output = locate_bundled_executables(
+        HostOS::Linux,
+        NodeCorepackMode::Off,
+        &["bin/npm", "bin/npx"],
+    );
+
+    assert!(output.secondary.get("npm").unwrap().no_shim);
+    assert!(output.secondary.get("npx").unwrap().no_shim);
+}
+
+#[test]
+fn locates_corepack_when_exposed() {
+    let output =
+        locate_bundled_executables(HostOS::Linux, NodeCorepackMode::Expose, &["bin/corepack"]);
+
+    let corepack = output.secondary.get("corepack").unwrap();
+
+    assert_eq!(
+        corepack.exe_path,
+        Some("lib/node_modules/corepack/dist/corepack.js".into())
+    );
+    assert_eq!(corepack.parent_exe_name, Some("node".into()));
+    assert!(corepack.no_bin);
+    assert!(!corepack.no_shim);
+}
+
+#[test]
+fn doesnt_shim_corepack_by_default() {
+    let output =
+        locate_bundled_executables(HostOS::Linux, NodeCorepackMode::Off, &["bin/corepack"]);
+
+    assert!(output.secondary.get("corepack").unwrap().no_shim);
+}
+
+fn locate_globals_dirs(mode: NodeGlobalsMode) -> Vec<String> {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::Linux, HostArch::X64);
+        config.tool_config(NodePluginConfig {
+            globals_mode: mode,
+            ..Default::default()
+        });
+    });
+
+    plugin
+        .locate_executables(LocateExecutablesInput {
+            context: ToolContext {
+                version: VersionSpec::parse("20.15.1").unwrap(),
+                ..Default::default()
+            },
+        })
+        .globals_lookup_dirs
+}
+
+#[test]
+#[serial]
+fn locates_shared_globals_dir() {
+    assert_eq!(
+        locate_globals_dirs(NodeGlobalsMode::Shared),
+        vec!["$PROTO_HOME/tools/node/globals/bin"]
+    );
+}
+
+#[test]
+#[serial]
+fn locates_per_major_globals_dir() {
+    assert_eq!(
+        locate_globals_dirs(NodeGlobalsMode::PerMajor),
+        vec!["$PROTO_HOME/tools/node/globals/20/bin"]
+    );
+}
+
+#[test]
+#[serial]
+fn locates_per_version_globals_dir() {
+    assert_eq!(
+        locate_globals_dirs(NodeGlobalsMode::PerVersion),
+        vec!["$PROTO_HOME/tools/node/globals/20.15.1/bin"]
+    );
+}
+
+#[test]
+#[serial]
+fn locates_globals_dir_from_env_var() {
+    env::set_var("PROTO_NODE_GLOBALS_MODE", "per-major");
+
+    let dirs = locate_globals_dirs(NodeGlobalsMode::Shared);
+
+    env::remove_var("PROTO_NODE_GLOBALS_MODE");
+
+    assert_eq!(dirs, vec!["$PROTO_HOME/tools/node/globals/20/bin"]);
+}
@@ -1,12 +1,20 @@
+#[cfg(not(windows))]
+mod utils;
+
 // Importing proto_pdk crashes Windows because it contains WASM code
 #[cfg(not(windows))]
 mod node_hooks {
-    use node_common::NodePluginConfig;
-    use proto_pdk::InstallHook;
+    use super::utils::start_unavailable_server;
+    use node_common::{
+        NodeBundledNpmMode, NodeCorepackMode, NodeEolPolicy, NodeGlobalsMode, NodeImportSource,
+        NodePluginConfig,
+    };
+    use proto_pdk::{InstallHook, RunHook};
     use proto_pdk_test_utils::*;
     use serial_test::serial;
-    use std::collections::HashSet;
+    use std::collections::{HashMap, HashSet};
     use std::env;
+    use std::fs;
     use std::path::PathBuf;
 
     fn set_vars(path: PathBuf) {
@@ -108,4 +116,1073 @@ mod node_hooks {
 
         assert!(!sandbox.path().join(".proto/tools/npm/8.6.0").exists());
     }
+
+    #[test]
+    #[serial]
+    fn copies_bundled_npm_from_node_installation() {
+        let sandbox = create_empty_proto_sandbox();
+        let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+            config.host(HostOS::Linux, HostArch::X64);
+            config.tool_config(NodePluginConfig {
+                bundled_npm: true,
+                bundled_npm_mode: NodeBundledNpmMode::Copy,
+                ..Default::default()
+            });
+        });
+
+        sandbox.create_file(
+            ".proto/tools/node-test/18.0.0/lib/node_modules/npm/package.json",
+            r#"{ "name": "npm", "version": "8.6.0" }"#,
+        );
+        sandbox.create_file(
+            ".proto/tools/node-test/18.0.0/lib/node_modules/npm/bin/npm-cli.js",
+            "",
+        );
+        // Only exists in the bundled package, not in the registry tarball
+        sandbox.create_file(
+            ".proto/tools/node-test/18.0.0/lib/node_modules/npm/copied.txt",
+            "",
+        );
+
+        // Use the npm plugin from this workspace, which does the copying
+        sandbox.create_file(
+            ".proto/.prototools",
+            format!(
+                "[plugins]\nnpm = \"file://{}\"\n",
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("../../target/wasm32-wasi/debug/node_depman_plugin.wasm")
+                    .display()
+            ),
+        );
+
+        set_vars(sandbox.path().join(".proto"));
+
+        plugin.post_install(InstallHook {
+            context: ToolContext {
+                version: VersionSpec::parse("18.0.0").unwrap(),
+                tool_dir: VirtualPath::OnlyReal(
+                    sandbox.path().join(".proto/tools/node-test/18.0.0"),
+                ),
+                ..Default::default()
+            },
+            ..InstallHook::default()
+        });
+
+        reset_vars();
+
+        assert!(sandbox
+            .path()
+            .join(".proto/tools/npm/8.6.0/copied.txt")
+            .exists());
+
+        let manifest =
+            ToolManifest::load(sandbox.path().join(".proto/tools/npm/manifest.json")).unwrap();
+
+        assert_eq!(
+            manifest.installed_versions,
+            HashSet::from_iter([VersionSpec::parse("8.6.0").unwrap()])
+        );
+
+        // Executables and shims are created by proto after the install
+        assert!(sandbox.path().join(".proto/shims/npm").exists());
+
+        assert_eq!(
+            fs::read_to_string(sandbox.path().join(".proto/tools/node/bundled-npm.json")).unwrap(),
+            "{\n  \"18.0.0\": \"8.6.0\"\n}"
+        );
+    }
+
+    // Emulate `node` to record the arguments instead of running corepack
+    fn enable_corepack(sandbox: &ProtoSandbox, exit_code: u8) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+            config.host(HostOS::Linux, HostArch::X64);
+            config.tool_config(NodePluginConfig {
+                corepack: NodeCorepackMode::Enable,
+                ..Default::default()
+            });
+        });
+
+        let tool_dir = sandbox.path().join(".proto/tools/node-test/20.0.0");
+
+        sandbox.create_file(
+            ".proto/tools/node-test/20.0.0/lib/node_modules/corepack/dist/corepack.js",
+            "",
+        );
+        sandbox.create_file(
+            ".proto/tools/node-test/20.0.0/bin/node",
+            format!("#!/bin/sh\necho \"$@\" > \"$(dirname \"$0\")/args.txt\"\nexit {exit_code}\n"),
+        );
+
+        fs::set_permissions(tool_dir.join("bin/node"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        plugin.post_install(InstallHook {
+            context: ToolContext {
+                version: VersionSpec::parse("20.0.0").unwrap(),
+                tool_dir: VirtualPath::OnlyReal(tool_dir.clone()),
+                ..Default::default()
+            },
+            ..InstallHook::default()
+        });
+
+        tool_dir
+    }
+
+    #[test]
+    fn enables_corepack_in_proto_directory() {
+        let sandbox = create_empty_proto_sandbox();
+        let tool_dir = enable_corepack(&sandbox, 0);
+        let corepack_dir = sandbox.path().join(".proto/tools/node/corepack");
+
+        assert!(corepack_dir.exists());
+        assert_eq!(
+            fs::read_to_string(tool_dir.join("bin/args.txt"))
+                .unwrap()
+                .trim(),
+            format!(
+                "{} enable --install-directory {}",
+                tool_dir
+                    .join("lib/node_modules/corepack/dist/corepack.js")
+                    .display(),
+                corepack_dir.display()
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to enable corepack for Node.js v20.0.0 (exit code 1)")]
+    fn errors_when_corepack_enable_fails() {
+        let sandbox = create_empty_proto_sandbox();
+
+        enable_corepack(&sandbox, 1);
+    }
+
+    #[test]
+    fn reinstalls_globals_from_previous_version() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let sandbox = create_empty_proto_sandbox();
+        let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+            config.host(HostOS::Linux, HostArch::X64);
+            config.tool_config(NodePluginConfig {
+                globals_mode: NodeGlobalsMode::PerMajor,
+                ..Default::default()
+            });
+        });
+
+        let tool_dir = sandbox.path().join(".proto/tools/node-test/22.0.0");
+        let globals_dir = sandbox.path().join(".proto/tools/node/globals");
+
+        // Redirected by the package managers
+        sandbox.create_file(
+            ".proto/tools/node/globals/20/lib/node_modules/prettier/package.json",
+            r#"{ "name": "prettier", "version": "3.0.0" }"#,
+        );
+
+        for (path, content) in [
+            (
+                "20.1.0/lib/node_modules/npm/package.json",
+                r#"{ "name": "npm" }"#,
+            ),
+            (
+                "20.1.0/lib/node_modules/typescript/package.json",
+                r#"{ "name": "typescript", "version": "5.4.5" }"#,
+            ),
+            (
+                "20.1.0/lib/node_modules/@scope/cli/package.json",
+                r#"{ "name": "@scope/cli", "version": "1.0.0" }"#,
+            ),
+            (
+                "18.0.0/lib/node_modules/eslint/package.json",
+                r#"{ "name": "eslint" }"#,
+            ),
+            ("22.0.0/lib/node_modules/npm/bin/npm-cli.js", ""),
+            (
+                "22.0.0/bin/node",
+                "#!/bin/sh\necho \"PREFIX=$PREFIX $@\" >> \"$(dirname \"$0\")/args.txt\"\n",
+            ),
+        ] {
+            sandbox.create_file(format!(".proto/tools/node-test/{path}"), content);
+        }
+
+        fs::set_permissions(tool_dir.join("bin/node"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        plugin.post_install(InstallHook {
+            context: ToolContext {
+                version: VersionSpec::parse("22.0.0").unwrap(),
+                tool_dir: VirtualPath::OnlyReal(tool_dir.clone()),
+                ..Default::default()
+            },
+            passthrough_args: vec!["--reinstall-globals-from=20".into()],
+            ..InstallHook::default()
+        });
+
+        let npm_cli = tool_dir.join("lib/node_modules/npm/bin/npm-cli.js");
+
+        assert_eq!(
+            fs::read_to_string(tool_dir.join("bin/args.txt"))
+                .unwrap()
+                .trim(),
+            format!(
+                "PREFIX= {} install --global @scope/cli@1.0.0 typescript@5.4.5\nPREFIX={} {} install --global prettier@3.0.0",
+                npm_cli.display(),
+                globals_dir.join("22").display(),
+                npm_cli.display()
+            )
+        );
+    }
+
+    mod pre_install {
+        use super::*;
+
+        fn create_schedule(sandbox: &ProtoSandbox) {
+            sandbox.create_file(
+                ".proto/tools/node/cache/schedule.json",
+                r#"{
+  "v14": { "start": "2020-04-21", "lts": "2020-10-27", "maintenance": "2021-10-19", "end": "2023-04-30", "codename": "Fermium" },
+  "v20": { "start": "2023-04-18", "lts": "2023-10-24", "maintenance": "2024-10-22", "end": "2099-04-30", "codename": "Iron" }
+}"#,
+            );
+        }
+
+        #[test]
+        #[should_panic(expected = "reached end-of-life on 2023-04-30, refusing to install")]
+        fn errors_for_eol_version_when_strict() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    eol_policy: NodeEolPolicy::Strict,
+                    ..Default::default()
+                });
+            });
+
+            create_schedule(&sandbox);
+
+            plugin.pre_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("14.21.3").unwrap(),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+        }
+
+        #[test]
+        fn allows_supported_version_when_strict() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    eol_policy: NodeEolPolicy::Strict,
+                    ..Default::default()
+                });
+            });
+
+            create_schedule(&sandbox);
+
+            plugin.pre_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("20.0.0").unwrap(),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+        }
+
+        #[test]
+        fn allows_eol_version_when_warning() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin("node-test");
+
+            create_schedule(&sandbox);
+
+            plugin.pre_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("14.21.3").unwrap(),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+        }
+
+        #[test]
+        fn doesnt_load_schedule_when_ignored() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    eol_policy: NodeEolPolicy::Ignore,
+                    ..Default::default()
+                });
+            });
+
+            plugin.pre_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("14.21.3").unwrap(),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+
+            assert!(!sandbox
+                .path()
+                .join(".proto/tools/node/cache/schedule.json")
+                .exists());
+        }
+
+        #[test]
+        fn allows_install_when_schedule_unavailable() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    schedule_url: format!("{}/schedule.json", start_unavailable_server()),
+                    ..Default::default()
+                });
+            });
+
+            plugin.pre_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("14.21.3").unwrap(),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+
+            assert!(!sandbox.path().join(".proto/tools/node/cache").exists());
+        }
+
+        #[test]
+        #[should_panic(expected = "received status 503")]
+        fn errors_when_schedule_unavailable_and_strict() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    eol_policy: NodeEolPolicy::Strict,
+                    schedule_url: format!("{}/schedule.json", start_unavailable_server()),
+                    ..Default::default()
+                });
+            });
+
+            plugin.pre_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("14.21.3").unwrap(),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+        }
+    }
+
+    mod pre_run {
+        use super::*;
+
+        fn create_hook(sandbox: &ProtoSandbox) -> RunHook {
+            sandbox.create_file(
+                ".proto/tools/node-test/20.0.0/include/node/node_version.h",
+                "#define NODE_MINOR_VERSION 0\n#define NODE_MODULE_VERSION 115\n",
+            );
+
+            RunHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("20.0.0").unwrap(),
+                    tool_dir: VirtualPath::OnlyReal(
+                        sandbox.path().join(".proto/tools/node-test/20.0.0"),
+                    ),
+                    ..Default::default()
+                },
+                ..RunHook::default()
+            }
+        }
+
+        // Host logs are captured by the test harness, so the hook is ran in a child
+        // process of this test binary, and its stderr is returned to the parent
+        fn check_abi_in_child(test_name: &str, with_node_modules: bool) -> Option<String> {
+            if env::var("NODE_ABI_CHECK_CHILD").is_err() {
+                let output = std::process::Command::new(env::current_exe().unwrap())
+                    .args([
+                        &format!("node_hooks::pre_run::{test_name}"),
+                        "--exact",
+                        "--nocapture",
+                    ])
+                    .env("NODE_ABI_CHECK_CHILD", "1")
+                    .output()
+                    .unwrap();
+
+                assert!(output.status.success());
+
+                return Some(String::from_utf8_lossy(&output.stderr).into_owned());
+            }
+
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin("node-test");
+
+            if with_node_modules {
+                sandbox.create_file("node_modules/.package-lock.json", "{}");
+            }
+
+            sandbox.create_file(
+                ".proto/tools/node/node-abi.json",
+                format!(r#"{{ "{}": 108 }}"#, sandbox.path().display()),
+            );
+
+            plugin.pre_run(create_hook(&sandbox));
+
+            None
+        }
+
+        #[test]
+        fn warns_when_abi_differs() {
+            if let Some(stderr) = check_abi_in_child("warns_when_abi_differs", true) {
+                assert!(stderr.contains(
+                    "Node.js v20.0.0 uses ABI 115 (NODE_MODULE_VERSION), but node_modules was installed with ABI 108"
+                ));
+            }
+        }
+
+        #[test]
+        fn does_nothing_without_node_modules() {
+            if let Some(stderr) = check_abi_in_child("does_nothing_without_node_modules", false) {
+                assert!(!stderr.contains("NODE_MODULE_VERSION"));
+            }
+        }
+
+        #[test]
+        fn doesnt_record_abi() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin("node-test");
+
+            sandbox.create_file("node_modules/.package-lock.json", "{}");
+
+            plugin.pre_run(create_hook(&sandbox));
+
+            assert!(!sandbox
+                .path()
+                .join(".proto/tools/node/node-abi.json")
+                .exists());
+        }
+
+        #[test]
+        fn ignores_abi_check_failures() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin("node-test");
+
+            sandbox.create_file("node_modules/.package-lock.json", "{}");
+            sandbox.create_file(".proto/tools/node/node-abi.json", "invalid");
+
+            // Would panic if the hook failed
+            plugin.pre_run(create_hook(&sandbox));
+
+            assert_eq!(
+                fs::read_to_string(sandbox.path().join(".proto/tools/node/node-abi.json")).unwrap(),
+                "invalid"
+            );
+        }
+
+        #[test]
+        #[serial]
+        fn sets_node_options_and_env() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    node_options: vec![
+                        "--max-old-space-size=8192".into(),
+                        "--enable-source-maps".into(),
+                    ],
+                    run_env: HashMap::from_iter([("FOO".into(), "bar".into())]),
+                    ..Default::default()
+                });
+            });
+
+            let result = plugin.pre_run(create_hook(&sandbox));
+            let env = result.env.unwrap();
+
+            assert_eq!(
+                env.get("NODE_OPTIONS").unwrap(),
+                "--max-old-space-size=8192 --enable-source-maps"
+            );
+            assert_eq!(env.get("FOO").unwrap(), "bar");
+        }
+
+        #[test]
+        #[serial]
+        fn skips_unsupported_node_options() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    node_options: vec![
+                        "--env-file=.env".into(),
+                        "--no-experimental-strip-types".into(),
+                    ],
+                    ..Default::default()
+                });
+            });
+
+            let result = plugin.pre_run(create_hook(&sandbox));
+
+            assert_eq!(result.env, None);
+        }
+
+        #[test]
+        #[serial]
+        fn appends_to_user_node_options() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    node_options: vec![
+                        "--max-old-space-size=8192".into(),
+                        "--enable-source-maps".into(),
+                    ],
+                    ..Default::default()
+                });
+            });
+
+            env::set_var("NODE_OPTIONS", "--inspect --max-old-space-size=1024");
+
+            let result = plugin.pre_run(create_hook(&sandbox));
+
+            env::remove_var("NODE_OPTIONS");
+
+            assert_eq!(
+                result.env.unwrap().get("NODE_OPTIONS").unwrap(),
+                "--inspect --max-old-space-size=1024 --enable-source-maps"
+            );
+        }
+
+        #[test]
+        fn sets_compile_cache_dir() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    compile_cache: true,
+                    ..Default::default()
+                });
+            });
+
+            let result = plugin.pre_run(RunHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("22.4.1").unwrap(),
+                    ..Default::default()
+                },
+                ..RunHook::default()
+            });
+
+            let cache_dir = sandbox
+                .path()
+                .join(".proto/tools/node/compile-cache/22.4.1");
+
+            assert!(cache_dir.exists());
+            assert_eq!(
+                result.env.unwrap().get("NODE_COMPILE_CACHE").unwrap(),
+                &cache_dir.to_string_lossy().to_string()
+            );
+        }
+
+        #[test]
+        fn doesnt_set_compile_cache_dir_when_unsupported() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.tool_config(NodePluginConfig {
+                    compile_cache: true,
+                    ..Default::default()
+                });
+            });
+
+            let result = plugin.pre_run(create_hook(&sandbox));
+
+            assert_eq!(result.env, None);
+            assert!(!sandbox
+                .path()
+                .join(".proto/tools/node/compile-cache")
+                .exists());
+        }
+    }
+
+    mod native_uninstall {
+        use super::*;
+
+        fn uninstall(sandbox: &ProtoSandbox, version: &str) -> NativeUninstallOutput {
+            let plugin = sandbox.create_plugin("node-test");
+
+            plugin.native_uninstall(NativeUninstallInput {
+                context: ToolContext {
+                    version: VersionSpec::parse(version).unwrap(),
+                    ..Default::default()
+                },
+            })
+        }
+
+        #[test]
+        fn removes_compile_cache_dir() {
+            let sandbox = create_empty_proto_sandbox();
+
+            sandbox.create_file(".proto/tools/node/compile-cache/22.4.1/entry", "");
+            sandbox.create_file(".proto/tools/node/compile-cache/22.5.0/entry", "");
+
+            let output = uninstall(&sandbox, "22.4.1");
+
+            assert!(output.uninstalled);
+            assert!(!sandbox
+                .path()
+                .join(".proto/tools/node/compile-cache/22.4.1")
+                .exists());
+            assert!(sandbox
+                .path()
+                .join(".proto/tools/node/compile-cache/22.5.0")
+                .exists());
+        }
+
+        #[test]
+        fn removes_version_globals_dirs() {
+            let sandbox = create_empty_proto_sandbox();
+
+            sandbox.create_file(".proto/tools/node-test/20.0.0/bin/node", "");
+            sandbox.create_file(".proto/tools/node/globals/20.0.0/bin/tsc", "");
+            sandbox.create_file(".proto/tools/node/globals/20/bin/tsc", "");
+            sandbox.create_file(".proto/tools/node/globals/bin/tsc", "");
+
+            uninstall(&sandbox, "20.0.0");
+
+            let globals_dir = sandbox.path().join(".proto/tools/node/globals");
+
+            assert!(!globals_dir.join("20.0.0").exists());
+            assert!(!globals_dir.join("20").exists());
+            assert!(globals_dir.join("bin").exists());
+        }
+
+        #[test]
+        fn keeps_major_globals_dir_when_used_by_another_version() {
+            let sandbox = create_empty_proto_sandbox();
+
+            sandbox.create_file(".proto/tools/node-test/20.0.0/bin/node", "");
+            sandbox.create_file(".proto/tools/node-test/20.1.0/bin/node", "");
+            sandbox.create_file(".proto/tools/node/globals/20/bin/tsc", "");
+
+            uninstall(&sandbox, "20.0.0");
+
+            assert!(sandbox.path().join(".proto/tools/node/globals/20").exists());
+        }
+
+        // Emulate `proto`, so that uninstalling npm doesn't touch the real proto
+        // home, and return the arguments it was called with (if it was called)
+        fn uninstall_npm(
+            sandbox: &ProtoSandbox,
+            state: &str,
+            exit_code: u8,
+        ) -> (NativeUninstallOutput, Option<String>) {
+            use std::os::unix::fs::PermissionsExt;
+
+            sandbox.create_file(".proto/tools/node/bundled-npm.json", state);
+            sandbox.create_file(
+                "bin/proto",
+                format!(
+                    "#!/bin/sh\necho \"$@\" > \"$(dirname \"$0\")/args.txt\"\nexit {exit_code}\n"
+                ),
+            );
+
+            fs::set_permissions(
+                sandbox.path().join("bin/proto"),
+                fs::Permissions::from_mode(0o755),
+            )
+            .unwrap();
+
+            let original_path = env::var("PATH").unwrap_or_default();
+
+            set_vars(sandbox.path().join(".proto"));
+            env::set_var(
+                "PATH",
+                format!("{}:{original_path}", sandbox.path().join("bin").display()),
+            );
+
+            let output = uninstall(sandbox, "18.0.0");
+
+            env::set_var("PATH", original_path);
+            reset_vars();
+
+            let args = fs::read_to_string(sandbox.path().join("bin/args.txt"))
+                .ok()
+                .map(|args| args.trim().to_owned());
+
+            (output, args)
+        }
+
+        fn read_npm_state(sandbox: &ProtoSandbox) -> String {
+            fs::read_to_string(sandbox.path().join(".proto/tools/node/bundled-npm.json")).unwrap()
+        }
+
+        #[test]
+        #[serial]
+        fn keeps_bundled_npm_when_used_by_another_version() {
+            let sandbox = create_empty_proto_sandbox();
+
+            let (_, args) =
+                uninstall_npm(&sandbox, r#"{ "18.0.0": "8.6.0", "18.1.0": "8.6.0" }"#, 0);
+
+            assert_eq!(args, None);
+            assert_eq!(read_npm_state(&sandbox), "{\n  \"18.1.0\": \"8.6.0\"\n}");
+        }
+
+        #[test]
+        #[serial]
+        fn keeps_bundled_npm_when_pinned() {
+            let sandbox = create_empty_proto_sandbox();
+
+            sandbox.create_file(".proto/.prototools", "node = \"18.0.0\"\nnpm = \"8.6.0\"\n");
+
+            let (_, args) = uninstall_npm(&sandbox, r#"{ "18.0.0": "8.6.0" }"#, 0);
+
+            assert_eq!(args, None);
+            assert_eq!(read_npm_state(&sandbox), "{}");
+        }
+
+        fn uninstalls_npm_with_config(prototools: &str) -> bool {
+            let sandbox = create_empty_proto_sandbox();
+
+            sandbox.create_file(".prototools", prototools);
+
+            let (_, args) = uninstall_npm(&sandbox, r#"{ "18.0.0": "8.6.0" }"#, 0);
+
+            args.is_some()
+        }
+
+        #[test]
+        #[serial]
+        fn keeps_bundled_npm_when_pinned_to_range_or_alias() {
+            assert!(!uninstalls_npm_with_config("npm = \"^8\"\n"));
+            assert!(!uninstalls_npm_with_config("npm = \"bundled\"\n"));
+        }
+
+        #[test]
+        #[serial]
+        fn uninstalls_bundled_npm_when_pinned_to_another_version() {
+            assert!(uninstalls_npm_with_config("\"npm\" = \"9.0.0\"\n"));
+        }
+
+        #[test]
+        #[serial]
+        fn ignores_npm_in_tables() {
+            assert!(uninstalls_npm_with_config(
+                "[plugins]\nnpm = \"source:https://example.com/npm.wasm\"\n\n[tools.npm]\nshared-globals-dir = true\n"
+            ));
+        }
+
+        #[test]
+        #[serial]
+        fn uninstalls_bundled_npm() {
+            let sandbox = create_empty_proto_sandbox();
+
+            let (output, args) = uninstall_npm(&sandbox, r#"{ "18.0.0": "8.6.0" }"#, 0);
+
+            assert!(output.uninstalled);
+            assert_eq!(args.as_deref(), Some("uninstall npm 8.6.0"));
+            assert_eq!(read_npm_state(&sandbox), "{}");
+        }
+
+        #[test]
+        #[serial]
+        fn keeps_tracking_bundled_npm_when_uninstall_fails() {
+            let sandbox = create_empty_proto_sandbox();
+
+            let (output, args) = uninstall_npm(&sandbox, r#"{ "18.0.0": "8.6.0" }"#, 1);
+
+            assert!(output.uninstalled);
+            assert!(args.is_some());
+            assert_eq!(read_npm_state(&sandbox), r#"{ "18.0.0": "8.6.0" }"#);
+        }
+    }
+
+    mod verify_install {
+        use super::*;
+        use std::os::unix::fs::PermissionsExt;
+
+        fn verify(stdout: &str, exit_code: u8) {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.host(HostOS::Linux, HostArch::X64);
+                config.tool_config(NodePluginConfig {
+                    verify_install: true,
+                    ..Default::default()
+                });
+            });
+
+            let tool_dir = sandbox.path().join(".proto/tools/node-test/20.0.0");
+
+            // Emulate `node --version` and `node -p process.versions`
+            sandbox.create_file(
+                ".proto/tools/node-test/20.0.0/bin/node",
+                format!(
+                    "#!/bin/sh\nif [ \"$1\" = \"--version\" ]; then echo v{}; else echo \"{stdout}\"; fi\nexit {exit_code}\n",
+                    stdout.split(' ').next().unwrap()
+                ),
+            );
+
+            fs::set_permissions(tool_dir.join("bin/node"), fs::Permissions::from_mode(0o755))
+                .unwrap();
+
+            plugin.post_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("20.0.0").unwrap(),
+                    tool_dir: VirtualPath::OnlyReal(tool_dir),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+        }
+
+        #[test]
+        fn passes_when_binary_matches() {
+            verify("20.0.0 x64", 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "the binary reports version 18.0.0")]
+        fn fails_when_version_differs() {
+            verify("18.0.0 x64", 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "the binary reports the arm64 architecture, expected x64")]
+        fn fails_when_arch_differs() {
+            verify("20.0.0 arm64", 0);
+        }
+
+        #[test]
+        #[should_panic(expected = "`node --version` failed with exit code 1")]
+        fn fails_when_binary_cant_run() {
+            verify("20.0.0 x64", 1);
+        }
+
+        #[test]
+        #[should_panic(expected = "does not exist")]
+        fn fails_when_binary_is_missing() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.host(HostOS::Linux, HostArch::X64);
+                config.tool_config(NodePluginConfig {
+                    verify_install: true,
+                    ..Default::default()
+                });
+            });
+
+            plugin.post_install(InstallHook {
+                context: ToolContext {
+                    version: VersionSpec::parse("20.0.0").unwrap(),
+                    tool_dir: VirtualPath::OnlyReal(
+                        sandbox.path().join(".proto/tools/node-test/20.0.0"),
+                    ),
+                    ..Default::default()
+                },
+                ..InstallHook::default()
+            });
+        }
+    }
+
+    mod system {
+        use super::*;
+        use std::os::unix::fs::PermissionsExt;
+
+        fn create_node(sandbox: &ProtoSandbox, path: &str, version: &str) {
+            sandbox.create_file(path, format!("#!/bin/sh\necho v{version}\n"));
+
+            fs::set_permissions(sandbox.path().join(path), fs::Permissions::from_mode(0o755))
+                .unwrap();
+        }
+
+        #[test]
+        #[serial]
+        fn installs_and_locates_system_node() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.host(HostOS::Linux, HostArch::X64);
+            });
+
+            // The proto shim comes first on PATH, and must be skipped
+            create_node(&sandbox, ".proto/shims/node", "20.0.0");
+            create_node(&sandbox, "usr/bin/node", "18.19.1");
+
+            let original_path = env::var("PATH").unwrap_or_default();
+
+            env::set_var(
+                "PATH",
+                format!(
+                    "{}:{}:{original_path}",
+                    sandbox.path().join(".proto/shims").display(),
+                    sandbox.path().join("usr/bin").display()
+                ),
+            );
+
+            let tool_dir = sandbox.path().join(".proto/tools/node-test/system");
+            let context = ToolContext {
+                version: VersionSpec::Alias("system".into()),
+                tool_dir: VirtualPath::OnlyReal(tool_dir.clone()),
+                ..Default::default()
+            };
+
+            let output = plugin.native_install(NativeInstallInput {
+                context: context.clone(),
+                install_dir: VirtualPath::WithReal {
+                    path: PathBuf::from("/proto/tools/node-test/system"),
+                    virtual_prefix: PathBuf::from("/proto"),
+                    real_prefix: sandbox.path().join(".proto"),
+                },
+            });
+
+            env::set_var("PATH", original_path);
+
+            let node_path = sandbox.path().join("usr/bin/node");
+
+            assert!(output.installed);
+            assert_eq!(
+                fs::read_to_string(tool_dir.join("system.json")).unwrap(),
+                format!(
+                    "{{\n  \"path\": \"{}\",\n  \"version\": \"18.19.1\"\n}}",
+                    node_path.display()
+                )
+            );
+
+            let output = plugin.locate_executables(LocateExecutablesInput { context });
+
+            assert_eq!(output.primary.unwrap().exe_path, Some(node_path));
+            assert_eq!(output.exes_dir, None);
+            assert!(output.secondary.is_empty());
+        }
+
+        #[test]
+        fn skips_native_install_for_other_versions() {
+            let sandbox = create_empty_proto_sandbox();
+            let plugin = sandbox.create_plugin("node-test");
+
+            let output = plugin.native_install(NativeInstallInput {
+                context: ToolContext {
+                    version: VersionSpec::parse("20.0.0").unwrap(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            });
+
+            assert!(!output.installed);
+            assert!(output.skip_install);
+        }
+    }
+
+    mod import_install {
+        use super::*;
+        use std::os::unix::fs::PermissionsExt;
+
+        fn create_nvm_install(sandbox: &ProtoSandbox, version: &str, reported: &str, arch: &str) {
+            let dir = format!(".home/.nvm/versions/node/v{version}");
+
+            // Prints `process.versions.node + ' ' + process.arch`
+            sandbox.create_file(
+                format!("{dir}/bin/node"),
+                format!("#!/bin/sh\necho {reported} {arch}\n"),
+            );
+            sandbox.create_file(
+                format!("{dir}/lib/node_modules/npm/package.json"),
+                r#"{ "name": "npm", "version": "9.6.4" }"#,
+            );
+            sandbox.create_file(format!("{dir}/lib/node_modules/npm/bin/npm-cli.js"), "");
+
+            let dir = sandbox.path().join(dir);
+
+            fs::set_permissions(dir.join("bin/node"), fs::Permissions::from_mode(0o755)).unwrap();
+            std::os::unix::fs::symlink(
+                "../lib/node_modules/npm/bin/npm-cli.js",
+                dir.join("bin/npm"),
+            )
+            .unwrap();
+        }
+
+        fn import(sandbox: &ProtoSandbox) -> NativeInstallOutput {
+            let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+                config.host(HostOS::Linux, HostArch::X64);
+                config.tool_config(NodePluginConfig {
+                    import_from: vec![NodeImportSource::Volta, NodeImportSource::Nvm],
+                    ..Default::default()
+                });
+            });
+
+            plugin.native_install(NativeInstallInput {
+                context: ToolContext {
+                    version: VersionSpec::parse("20.0.0").unwrap(),
+                    ..Default::default()
+                },
+                install_dir: VirtualPath::WithReal {
+                    path: PathBuf::from("/proto/tools/node-test/20.0.0"),
+                    virtual_prefix: PathBuf::from("/proto"),
+                    real_prefix: sandbox.path().join(".proto"),
+                },
+            })
+        }
+
+        #[test]
+        fn imports_from_nvm() {
+            let sandbox = create_empty_proto_sandbox();
+
+            create_nvm_install(&sandbox, "20.0.0", "20.0.0", "x64");
+
+            let output = import(&sandbox);
+            let install_dir = sandbox.path().join(".proto/tools/node-test/20.0.0");
+
+            assert!(output.installed);
+            assert!(!output.skip_install);
+            assert!(
+                fs::metadata(install_dir.join("bin/node"))
+                    .unwrap()
+                    .permissions()
+                    .mode()
+                    & 0o111
+                    != 0
+            );
+            assert!(fs::symlink_metadata(install_dir.join("bin/npm"))
+                .unwrap()
+                .file_type()
+                .is_symlink());
+            assert!(install_dir
+                .join("lib/node_modules/npm/package.json")
+                .exists());
+        }
+
+        #[test]
+        fn downloads_when_not_found() {
+            let sandbox = create_empty_proto_sandbox();
+
+            create_nvm_install(&sandbox, "18.0.0", "18.0.0", "x64");
+
+            let output = import(&sandbox);
+
+            assert!(!output.installed);
+            assert!(output.skip_install);
+        }
+
+        #[test]
+        fn downloads_when_binary_version_differs() {
+            let sandbox = create_empty_proto_sandbox();
+
+            create_nvm_install(&sandbox, "20.0.0", "18.0.0", "x64");
+
+            let output = import(&sandbox);
+
+            assert!(!output.installed);
+            assert!(output.skip_install);
+            assert!(!sandbox
+                .path()
+                .join(".proto/tools/node-test/20.0.0")
+                .exists());
+        }
+
+        #[test]
+        fn downloads_when_binary_arch_differs() {
+            let sandbox = create_empty_proto_sandbox();
+
+            create_nvm_install(&sandbox, "20.0.0", "20.0.0", "arm64");
+
+            let output = import(&sandbox);
+
+            assert!(!output.installed);
+            assert!(output.skip_install);
+            assert!(!sandbox
+                .path()
+                .join(".proto/tools/node-test/20.0.0")
+                .exists());
+        }
+    }
 }
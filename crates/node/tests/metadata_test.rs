@@ -1,3 +1,4 @@
+use node_common::NodePluginConfig;
 use proto_pdk_test_utils::*;
 
 #[test]
@@ -14,3 +15,40 @@ fn registers_metadata() {
         }
     );
 }
+
+#[test]
+fn registers_version_suffix_when_overriding_arch() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::MacOS, HostArch::Arm64);
+        config.tool_config(NodePluginConfig {
+            arch: Some(HostArch::X64),
+            ..Default::default()
+        });
+    });
+
+    assert_eq!(
+        plugin.register_tool(ToolMetadataInput::default()).inventory,
+        ToolInventoryMetadata {
+            version_suffix: Some("-x64".into()),
+            ..ToolInventoryMetadata::default()
+        }
+    );
+}
+
+#[test]
+fn doesnt_register_version_suffix_when_arch_matches_host() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.host(HostOS::MacOS, HostArch::Arm64);
+        config.tool_config(NodePluginConfig {
+            arch: Some(HostArch::Arm64),
+            ..Default::default()
+        });
+    });
+
+    assert_eq!(
+        plugin.register_tool(ToolMetadataInput::default()).inventory,
+        ToolInventoryMetadata::default()
+    );
+}
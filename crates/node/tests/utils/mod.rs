@@ -0,0 +1,22 @@
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::thread;
+
+/// Start a local server that responds to every request with a 503,
+/// to simulate an unavailable remote, and return its base URL.
+pub fn start_unavailable_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut buffer = [0; 4096];
+            let _ = stream.read(&mut buffer);
+            let _ = stream.write_all(
+                b"HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            );
+        }
+    });
+
+    url
+}
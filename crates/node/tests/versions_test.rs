@@ -1,4 +1,14 @@
+mod utils;
+
+use node_common::{
+    get_release_schedule_cache_path, parse_date, today, NodePluginConfig, NodeRangePolicy,
+    NodeRangeStrategy,
+};
 use proto_pdk_test_utils::*;
+use serial_test::serial;
+use std::env;
+use std::fs;
+use std::time::{Duration, SystemTime};
 
 generate_resolve_versions_tests!("node-test", {
     "8" => "8.17.0",
@@ -9,6 +19,8 @@ generate_resolve_versions_tests!("node-test", {
     "node" => "22.4.1",
 });
 
+const PHASE_ALIASES: [&str; 3] = ["current", "active-lts", "maintenance-lts"];
+
 #[test]
 fn loads_versions_from_dist_url() {
     let sandbox = create_empty_proto_sandbox();
@@ -19,6 +31,137 @@ fn loads_versions_from_dist_url() {
     assert!(!output.versions.is_empty());
 }
 
+#[test]
+fn skips_versions_within_min_release_age() {
+    let sandbox = create_empty_proto_sandbox();
+    sandbox.create_file(".proto/tools/node/cache/schedule.json", "{}");
+
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            // Everything released in 2019 or later
+            min_release_age: (today() - parse_date("2018-12-31").unwrap()) as u32,
+            ..Default::default()
+        });
+    });
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    assert!(!output.versions.is_empty());
+    assert!(output.versions.iter().all(|version| match version {
+        VersionSpec::Semantic(version) => version.major < 12,
+        _ => false,
+    }));
+
+    let Some(UnresolvedVersionSpec::Semantic(latest)) = output.latest else {
+        panic!("Expected a semantic version");
+    };
+
+    assert!(latest.major < 12);
+    assert!(!output.aliases.contains_key("hydrogen"));
+}
+
+#[test]
+#[should_panic(expected = "No Node.js versions were released more than 36500 days ago")]
+fn errors_when_min_release_age_excludes_every_version() {
+    let sandbox = create_empty_proto_sandbox();
+    sandbox.create_file(".proto/tools/node/cache/schedule.json", "{}");
+
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            min_release_age: 36500,
+            ..Default::default()
+        });
+    });
+
+    plugin.load_versions(LoadVersionsInput::default());
+}
+
+fn create_cached_index(sandbox: &ProtoSandbox) {
+    sandbox.create_file(".proto/tools/node/cache/schedule.json", "{}");
+    sandbox.create_file(
+        ".proto/tools/node/cache/index.json",
+        r#"[{ "version": "v99.0.0", "date": "2099-01-01", "lts": false }]"#,
+    );
+}
+
+#[test]
+fn loads_versions_from_cached_index() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    create_cached_index(&sandbox);
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    assert_eq!(output.versions, [VersionSpec::parse("99.0.0").unwrap()]);
+}
+
+#[test]
+#[serial]
+fn loads_versions_from_stale_cached_index_when_offline() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            index_cache_ttl: 0,
+            ..Default::default()
+        });
+    });
+
+    create_cached_index(&sandbox);
+
+    env::set_var("PROTO_OFFLINE", "1");
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    env::remove_var("PROTO_OFFLINE");
+
+    assert_eq!(output.versions, [VersionSpec::parse("99.0.0").unwrap()]);
+}
+
+#[test]
+fn refetches_invalid_cached_index() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    sandbox.create_file(".proto/tools/node/cache/schedule.json", "{}");
+    sandbox.create_file(".proto/tools/node/cache/index.json", r#"[{ "version": "#);
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    assert!(output.versions.len() > 1);
+    assert!(
+        fs::read_to_string(sandbox.path().join(".proto/tools/node/cache/index.json"))
+            .unwrap()
+            .trim_end()
+            .ends_with(']')
+    );
+}
+
+#[test]
+fn refreshes_stale_cached_index() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            index_cache_ttl: 0,
+            ..Default::default()
+        });
+    });
+
+    create_cached_index(&sandbox);
+
+    // Cached long before the index was last modified
+    fs::File::options()
+        .write(true)
+        .open(sandbox.path().join(".proto/tools/node/cache/index.json"))
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(946684800))
+        .unwrap();
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    assert!(output.versions.len() > 1);
+}
+
 #[test]
 fn sets_latest_alias() {
     let sandbox = create_empty_proto_sandbox();
@@ -37,7 +180,16 @@ fn sets_lts_aliases() {
     let plugin = sandbox.create_plugin("node-test");
 
     let output = plugin.load_versions(LoadVersionsInput::default());
-    let mut aliases = output.aliases.keys().collect::<Vec<_>>();
+    let mut aliases = output
+        .aliases
+        .keys()
+        .filter(|alias| {
+            !alias.contains("security")
+                && !alias.starts_with("lts--")
+                && !alias.starts_with("abi-")
+                && !PHASE_ALIASES.contains(&alias.as_str())
+        })
+        .collect::<Vec<_>>();
     aliases.sort();
 
     assert_eq!(
@@ -49,6 +201,305 @@ fn sets_lts_aliases() {
     );
 }
 
+#[test]
+fn sets_relative_lts_aliases() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+    let get_major = |alias: &str| match output.aliases.get(alias).unwrap() {
+        UnresolvedVersionSpec::Semantic(version) => version.major,
+        _ => panic!("Expected a semantic version"),
+    };
+
+    assert!(!output.aliases.contains_key("lts--0"));
+    assert!(get_major("lts--1") < get_major("stable"));
+    assert!(get_major("lts--2") < get_major("lts--1"));
+}
+
+#[test]
+fn resolves_relative_lts_aliases() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    for (alias, candidate) in [
+        ("lts/-1", "lts--1"),
+        ("lts--1", "lts--1"),
+        ("lts/-2", "lts--2"),
+        ("lts/-0", "stable"),
+    ] {
+        assert_eq!(
+            plugin.resolve_version(ResolveVersionInput {
+                initial: UnresolvedVersionSpec::Alias(alias.into()),
+            }),
+            ResolveVersionOutput {
+                candidate: Some(UnresolvedVersionSpec::Alias(candidate.into())),
+                ..Default::default()
+            }
+        );
+    }
+}
+
+#[test]
+fn sets_abi_aliases() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    let UnresolvedVersionSpec::Semantic(version) = output.aliases.get("abi-108").unwrap() else {
+        panic!("Expected a semantic version");
+    };
+
+    assert_eq!(version.major, 18);
+    assert_eq!(
+        output.aliases.get("abi-93"),
+        Some(&UnresolvedVersionSpec::parse("16.20.2").unwrap())
+    );
+}
+
+#[test]
+fn sets_release_phase_aliases() {
+    let sandbox = create_empty_proto_sandbox();
+    sandbox.create_file(
+        ".proto/tools/node/cache/schedule.json",
+        r#"{
+  "v16": { "start": "2021-04-20", "lts": "2021-10-26", "maintenance": "2022-10-18", "end": "2023-09-11", "codename": "Gallium" },
+  "v18": { "start": "2022-04-19", "lts": "2022-10-25", "maintenance": "2023-10-18", "end": "2999-04-30", "codename": "Hydrogen" },
+  "v20": { "start": "2023-04-18", "lts": "2023-10-24", "maintenance": "2999-10-22", "end": "2999-04-30", "codename": "Iron" },
+  "v22": { "start": "2024-04-24", "lts": "2999-10-29", "maintenance": "2999-10-21", "end": "2999-04-30", "codename": "" }
+}"#,
+    );
+
+    let plugin = sandbox.create_plugin("node-test");
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    for (alias, major) in [("current", 22), ("active-lts", 20), ("maintenance-lts", 18)] {
+        let UnresolvedVersionSpec::Semantic(version) = output.aliases.get(alias).unwrap() else {
+            panic!("Expected a semantic version");
+        };
+
+        assert_eq!(version.major, major);
+    }
+}
+
+#[test]
+fn sets_indexed_maintenance_aliases() {
+    let sandbox = create_empty_proto_sandbox();
+    sandbox.create_file(
+        ".proto/tools/node/cache/schedule.json",
+        r#"{
+  "v16": { "start": "2021-04-20", "lts": "2021-10-26", "maintenance": "2022-10-18", "end": "2999-09-11", "codename": "Gallium" },
+  "v18": { "start": "2022-04-19", "lts": "2022-10-25", "maintenance": "2023-10-18", "end": "2999-04-30", "codename": "Hydrogen" },
+  "v20": { "start": "2023-04-18", "lts": "2023-10-24", "maintenance": "2999-10-22", "end": "2999-04-30", "codename": "Iron" }
+}"#,
+    );
+
+    let plugin = sandbox.create_plugin("node-test");
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    for (alias, major) in [("maintenance-lts", 18), ("maintenance-lts--1", 16)] {
+        let UnresolvedVersionSpec::Semantic(version) = output.aliases.get(alias).unwrap() else {
+            panic!("Expected a semantic version");
+        };
+
+        assert_eq!(version.major, major);
+    }
+
+    assert!(!output.aliases.contains_key("maintenance-lts--2"));
+}
+
+// Relative to the sandbox
+fn get_schedule_cache_file(url: &str) -> String {
+    format!(
+        ".proto/{}",
+        get_release_schedule_cache_path(url)
+            .strip_prefix("/proto")
+            .unwrap()
+            .display()
+    )
+}
+
+#[test]
+fn sets_release_phase_aliases_from_stale_schedule_when_unavailable() {
+    let sandbox = create_empty_proto_sandbox();
+    let schedule_url = format!("{}/schedule.json", utils::start_unavailable_server());
+    let cache_file = get_schedule_cache_file(&schedule_url);
+
+    sandbox.create_file(
+        &cache_file,
+        r#"{
+  "v20": { "start": "2023-04-18", "lts": "2023-10-24", "maintenance": "2999-10-22", "end": "2999-04-30", "codename": "Iron" }
+}"#,
+    );
+
+    fs::File::options()
+        .write(true)
+        .open(sandbox.path().join(&cache_file))
+        .unwrap()
+        .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(946684800))
+        .unwrap();
+
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            schedule_url: schedule_url.clone(),
+            ..Default::default()
+        });
+    });
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    let UnresolvedVersionSpec::Semantic(version) = output.aliases.get("active-lts").unwrap() else {
+        panic!("Expected a semantic version");
+    };
+
+    assert_eq!(version.major, 20);
+}
+
+#[test]
+fn doesnt_use_schedule_cached_for_another_url() {
+    let sandbox = create_empty_proto_sandbox();
+    sandbox.create_file(
+        ".proto/tools/node/cache/schedule.json",
+        r#"{
+  "v20": { "start": "2023-04-18", "lts": "2023-10-24", "maintenance": "2999-10-22", "end": "2999-04-30", "codename": "Iron" }
+}"#,
+    );
+
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            schedule_url: format!("{}/schedule.json", utils::start_unavailable_server()),
+            ..Default::default()
+        });
+    });
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    assert!(!output.aliases.contains_key("active-lts"));
+}
+
+#[test]
+fn sets_security_aliases() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    assert!(output.aliases.contains_key("security"));
+    assert!(output.aliases.contains_key("lts-security"));
+
+    let UnresolvedVersionSpec::Semantic(version) = output.aliases.get("security-20").unwrap()
+    else {
+        panic!("Expected a semantic version");
+    };
+
+    assert_eq!(version.major, 20);
+}
+
+#[test]
+fn resolves_line_security_alias_from_config() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    // Parse the same way proto parses `node = "security-20"` in `.prototools`
+    let initial = UnresolvedVersionSpec::parse("security-20").unwrap();
+
+    assert_eq!(initial, UnresolvedVersionSpec::Alias("security-20".into()));
+    assert_eq!(
+        plugin.resolve_version(ResolveVersionInput { initial }),
+        ResolveVersionOutput::default()
+    );
+
+    let output = plugin.load_versions(LoadVersionsInput::default());
+
+    assert!(output.aliases.contains_key("security-20"));
+}
+
+#[test]
+fn resolves_lts_security_alias() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    for alias in ["lts-security", "lts/security"] {
+        assert_eq!(
+            plugin.resolve_version(ResolveVersionInput {
+                initial: UnresolvedVersionSpec::Alias(alias.into()),
+            }),
+            ResolveVersionOutput {
+                candidate: Some(UnresolvedVersionSpec::Alias("lts-security".into())),
+                ..Default::default()
+            }
+        );
+    }
+}
+
+#[test]
+fn resolves_system_alias() {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin("node-test");
+
+    assert_eq!(
+        plugin.resolve_version(ResolveVersionInput {
+            initial: UnresolvedVersionSpec::Alias("system".into()),
+        }),
+        ResolveVersionOutput {
+            version: Some(VersionSpec::Alias("system".into())),
+            ..Default::default()
+        }
+    );
+}
+
+fn resolve_range_with_policy(
+    policy: NodeRangePolicy,
+    range: &str,
+) -> Option<UnresolvedVersionSpec> {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            range_policy: policy,
+            ..Default::default()
+        });
+    });
+
+    plugin
+        .resolve_version(ResolveVersionInput {
+            initial: UnresolvedVersionSpec::parse(range).unwrap(),
+        })
+        .candidate
+}
+
+#[test]
+fn resolves_range_to_highest_by_default() {
+    assert_eq!(
+        resolve_range_with_policy(NodeRangePolicy::Highest, "^16 || ^17"),
+        None
+    );
+}
+
+#[test]
+fn resolves_range_to_lts_when_preferred() {
+    assert_eq!(
+        resolve_range_with_policy(NodeRangePolicy::PreferLts, "^16 || ^17"),
+        Some(UnresolvedVersionSpec::parse("16.20.2").unwrap())
+    );
+}
+
+#[test]
+fn resolves_range_to_non_lts_when_no_lts_matches() {
+    assert_eq!(
+        resolve_range_with_policy(NodeRangePolicy::PreferLts, "^17"),
+        None
+    );
+}
+
+#[test]
+#[should_panic(expected = "No Node.js LTS version satisfies ^17")]
+fn errors_for_range_without_lts_when_lts_only() {
+    resolve_range_with_policy(NodeRangePolicy::LtsOnly, "^17");
+}
+
 #[test]
 fn parses_engines() {
     let sandbox = create_empty_proto_sandbox();
@@ -65,6 +516,80 @@ fn parses_engines() {
     );
 }
 
+fn parse_engines_with_lowest_strategy(engines: &str) -> Option<UnresolvedVersionSpec> {
+    parse_engines_with_lowest_strategy_and_policy(engines, NodeRangePolicy::Highest)
+}
+
+fn parse_engines_with_lowest_strategy_and_policy(
+    engines: &str,
+    policy: NodeRangePolicy,
+) -> Option<UnresolvedVersionSpec> {
+    let sandbox = create_empty_proto_sandbox();
+    let plugin = sandbox.create_plugin_with_config("node-test", |config| {
+        config.tool_config(NodePluginConfig {
+            range_policy: policy,
+            range_strategy: NodeRangeStrategy::Lowest,
+            ..Default::default()
+        });
+    });
+
+    plugin
+        .parse_version_file(ParseVersionFileInput {
+            content: format!(r#"{{ "engines": {{ "node": "{engines}" }} }}"#),
+            file: "package.json".into(),
+        })
+        .version
+}
+
+#[test]
+#[serial]
+fn parses_engines_to_lowest_version() {
+    assert_eq!(
+        parse_engines_with_lowest_strategy("^4"),
+        Some(UnresolvedVersionSpec::parse("4.0.0").unwrap())
+    );
+
+    // Exact versions are left as-is
+    assert_eq!(
+        parse_engines_with_lowest_strategy("16.20.2"),
+        Some(UnresolvedVersionSpec::parse("16.20.2").unwrap())
+    );
+}
+
+#[test]
+#[serial]
+fn parses_engines_to_lowest_lts_version() {
+    assert_eq!(
+        parse_engines_with_lowest_strategy_and_policy("^17 || ^18", NodeRangePolicy::LtsOnly),
+        Some(UnresolvedVersionSpec::parse("18.12.0").unwrap())
+    );
+
+    // Falls back to a non-LTS version
+    assert_eq!(
+        parse_engines_with_lowest_strategy_and_policy("^17", NodeRangePolicy::PreferLts),
+        Some(UnresolvedVersionSpec::parse("17.0.0").unwrap())
+    );
+}
+
+#[test]
+#[serial]
+#[should_panic(expected = "No Node.js LTS version satisfies ^17")]
+fn errors_for_lowest_range_without_lts_when_lts_only() {
+    parse_engines_with_lowest_strategy_and_policy("^17", NodeRangePolicy::LtsOnly);
+}
+
+#[test]
+#[serial]
+fn parses_engines_with_strategy_from_env() {
+    env::set_var("PROTO_NODE_RANGE_STRATEGY", "highest");
+
+    let version = parse_engines_with_lowest_strategy("^4");
+
+    env::remove_var("PROTO_NODE_RANGE_STRATEGY");
+
+    assert_eq!(version, Some(UnresolvedVersionSpec::parse("^4").unwrap()));
+}
+
 #[test]
 fn parses_volta() {
     let sandbox = create_empty_proto_sandbox();